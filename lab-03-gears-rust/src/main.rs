@@ -1,10 +1,9 @@
 use opencv::{
     core::{
         absdiff, bitwise_and, bitwise_not, bitwise_or, no_array, Mat, Point, Rect, Scalar,
-        BORDER_CONSTANT, CV_8UC1,
+        BORDER_CONSTANT, CV_32F, CV_8UC1,
     },
     highgui,
-    imgcodecs::{self, IMREAD_GRAYSCALE},
     imgproc::{
         circle, dilate, erode, flood_fill, morphology_default_border_value, threshold, FILLED,
         THRESH_BINARY,
@@ -13,18 +12,63 @@ use opencv::{
     Result,
 };
 
+#[path = "../../shared/cli.rs"]
+mod cli;
+#[path = "../../shared/fft.rs"]
+mod fft;
+#[path = "../../shared/filters.rs"]
+mod filters;
+#[path = "../../shared/homomorphic.rs"]
+mod homomorphic;
+#[path = "../../shared/image_io.rs"]
+mod image_io;
+#[path = "../../shared/normalization.rs"]
+mod normalization;
+use cli::{arg, arg_choice, arg_value, display_normalization, save, show};
+use filters::HighEmphasis;
+use homomorphic::{homomorphic, Homomorphic};
+use image_io::load_float;
+use normalization::normalize;
+
 fn main() -> Result<()> {
-    highgui::named_window("gears", 0)?;
+    let image_float = load_float(
+        &arg("image").unwrap_or_else(|| "./Gears.png".to_string()),
+        1.0,
+    )?;
 
-    let image_file = imgcodecs::imread("./Gears.png", IMREAD_GRAYSCALE)?;
+    // With `homomorphic=gaussian|butterworth` uneven lighting is evened out
+    // before the threshold below.
+    let image_float = match arg_choice("homomorphic", HighEmphasis::NAMES, HighEmphasis::by_name)? {
+        Some(filter) => {
+            let image_corrected = homomorphic(
+                &image_float,
+                &Homomorphic {
+                    filter,
+                    low: 0.5,
+                    high: 1.5,
+                },
+            )?;
+            let (image_display, _) = normalize(&image_corrected, display_normalization()?, 1.0)?;
+            show("homomorphic", &image_display, 1.0)?;
+            image_corrected
+        }
+        None => image_float,
+    };
+    let image_file = {
+        let mut clone = new_mat();
+        image_float.convert_to(&mut clone, CV_8UC1, 255.0, 0.0)?;
+        clone
+    };
 
+    // `threshold=` is on the 0..255 scale of the image.
     let image_bin = {
         let mut binary = image_file.clone();
-        threshold(&image_file, &mut binary, 100.0, 255.0, THRESH_BINARY)?;
+        let level = arg_value("threshold")?.unwrap_or(100.0);
+        threshold(&image_file, &mut binary, level, 255.0, THRESH_BINARY)?;
         binary
     };
 
-    highgui::imshow("gears", &image_bin)?;
+    show("gears", &image_bin, 255.0)?;
     highgui::wait_key(-1)?;
 
     let image_filled = {
@@ -32,27 +76,27 @@ fn main() -> Result<()> {
         or_image(&image_bin, &not_image(&flooded)?)?
     };
 
-    highgui::imshow("gears", &image_filled)?;
+    show("gears", &image_filled, 255.0)?;
     highgui::wait_key(-1)?;
 
     let image_diff = diff_image(&opening(&image_filled, 15)?, &image_filled)?;
 
-    highgui::imshow("gears", &image_diff)?;
+    show("gears", &image_diff, 255.0)?;
     highgui::wait_key(-1)?;
 
     let image_cleared_diff = closing(&image_diff, 3)?;
 
-    highgui::imshow("gears", &image_cleared_diff)?;
+    show("gears", &image_cleared_diff, 255.0)?;
     highgui::wait_key(-1)?;
 
     let image_ring = &dilated_image(&image_cleared_diff, 7, 2)?;
 
-    highgui::imshow("gears", &image_ring)?;
+    show("gears", &image_ring, 255.0)?;
     highgui::wait_key(-1)?;
 
     let image_cleared_ring = &opening(&image_ring, 7)?;
 
-    highgui::imshow("gears", &image_cleared_ring)?;
+    show("gears", &image_cleared_ring, 255.0)?;
     highgui::wait_key(-1)?;
 
     let dilated_diff = eroded_image(&dilated_image(&image_diff, 7, 3)?, 7)?;
@@ -64,7 +108,8 @@ fn main() -> Result<()> {
 
     let result = or_image(&break_points, &image_cleared_ring)?;
 
-    highgui::imshow("gears", &result)?;
+    show("gears", &result, 255.0)?;
+    save("gears", &result, 255.0)?;
     highgui::wait_key(-1)?;
 
     Ok(())
@@ -166,3 +211,16 @@ fn diff_image(left: &Mat, right: &Mat) -> Result<Mat> {
     absdiff(&left, &right, &mut clone)?;
     Ok(clone)
 }
+
+// For the shared modules, which cannot assume whether the getter is fallible.
+fn depth(image: &Mat) -> Result<i32> {
+    image.depth()
+}
+
+fn new_mat() -> Mat {
+    Mat::zeros(0, 0, CV_32F).unwrap().to_mat().unwrap()
+}
+
+fn mul_mat_image(image: &Mat, mul: &Mat) -> Result<Mat> {
+    Ok(image.mul(mul, 1.0)?.to_mat()?)
+}
@@ -0,0 +1,233 @@
+use opencv::{
+    core::{
+        add, divide2, multiply, no_array, subtract, Mat, Point, Size, BORDER_DEFAULT, CV_32F,
+        CV_8UC1,
+    },
+    imgproc::{bilateral_filter, box_filter, median_blur},
+    photo::fast_nl_means_denoising,
+    prelude::*,
+    Result,
+};
+
+#[derive(Debug, Clone, Copy)]
+pub enum Denoise {
+    Median {
+        size: i32,
+    },
+    Bilateral {
+        diameter: i32,
+        sigma_color: f64,
+        sigma_space: f64,
+    },
+    // `eps` is measured on the guide scaled to [0, 1].
+    Guided {
+        radius: i32,
+        eps: f64,
+    },
+    NonLocalMeans {
+        h: f32,
+        template_size: i32,
+        search_size: i32,
+    },
+}
+
+impl Denoise {
+    pub const NAMES: &'static [&'static str] = &["median", "bilateral", "guided", "nlm"];
+
+    pub fn by_name(name: &str) -> Option<Denoise> {
+        match name {
+            "median" => Some(Denoise::Median { size: 5 }),
+            "bilateral" => Some(Denoise::Bilateral {
+                diameter: 9,
+                sigma_color: 40.0,
+                sigma_space: 5.0,
+            }),
+            "guided" => Some(Denoise::Guided {
+                radius: 4,
+                eps: 0.01,
+            }),
+            "nlm" => Some(Denoise::NonLocalMeans {
+                h: 15.0,
+                template_size: 7,
+                search_size: 21,
+            }),
+            _ => None,
+        }
+    }
+}
+
+// Works on CV_32F images in the 0..255 range; `guide` is only used by the guided filter.
+pub fn denoise(image: &Mat, guide: &Mat, method: &Denoise) -> Result<Mat> {
+    match *method {
+        Denoise::Median { size } => {
+            let mut clone = image.clone();
+            median_blur(image, &mut clone, size)?;
+            Ok(clone)
+        }
+        Denoise::Bilateral {
+            diameter,
+            sigma_color,
+            sigma_space,
+        } => {
+            let mut clone = image.clone();
+            bilateral_filter(
+                image,
+                &mut clone,
+                diameter,
+                sigma_color,
+                sigma_space,
+                BORDER_DEFAULT,
+            )?;
+            Ok(clone)
+        }
+        Denoise::Guided { radius, eps } => guided_filter(image, guide, radius, eps),
+        Denoise::NonLocalMeans {
+            h,
+            template_size,
+            search_size,
+        } => {
+            let mut image_8u = image.clone();
+            image.convert_to(&mut image_8u, CV_8UC1, 1.0, 0.0)?;
+
+            let mut denoised = image_8u.clone();
+            fast_nl_means_denoising(&image_8u, &mut denoised, h, template_size, search_size)?;
+
+            let mut clone = image.clone();
+            denoised.convert_to(&mut clone, CV_32F, 1.0, 0.0)?;
+            Ok(clone)
+        }
+    }
+}
+
+// He, Sun, Tang: "Guided Image Filtering".
+fn guided_filter(image: &Mat, guide: &Mat, radius: i32, eps: f64) -> Result<Mat> {
+    let guide = {
+        let mut clone = guide.clone();
+        guide.convert_to(&mut clone, CV_32F, 1.0 / 255.0, 0.0)?;
+        clone
+    };
+    let image = {
+        let mut clone = image.clone();
+        image.convert_to(&mut clone, CV_32F, 1.0, 0.0)?;
+        clone
+    };
+
+    let mean_guide = box_mean(&guide, radius)?;
+    let mean_image = box_mean(&image, radius)?;
+    let corr_guide = box_mean(&mul_images(&guide, &guide)?, radius)?;
+    let corr_guide_image = box_mean(&mul_images(&guide, &image)?, radius)?;
+
+    let var_guide = sub_images(&corr_guide, &mul_images(&mean_guide, &mean_guide)?)?;
+    let cov_guide_image = sub_images(&corr_guide_image, &mul_images(&mean_guide, &mean_image)?)?;
+
+    let a = {
+        let mut var_eps = var_guide.clone();
+        var_guide.convert_to(&mut var_eps, CV_32F, 1.0, eps)?;
+        let mut clone = var_guide.clone();
+        divide2(&cov_guide_image, &var_eps, &mut clone, 1.0, -1)?;
+        clone
+    };
+    let b = sub_images(&mean_image, &mul_images(&a, &mean_guide)?)?;
+
+    let mut result = image.clone();
+    add(
+        &mul_images(&box_mean(&a, radius)?, &guide)?,
+        &box_mean(&b, radius)?,
+        &mut result,
+        &no_array()?,
+        CV_32F,
+    )?;
+    Ok(result)
+}
+
+fn box_mean(image: &Mat, radius: i32) -> Result<Mat> {
+    let mut clone = image.clone();
+    box_filter(
+        image,
+        &mut clone,
+        CV_32F,
+        Size::new(2 * radius + 1, 2 * radius + 1),
+        Point::new(-1, -1),
+        true,
+        BORDER_DEFAULT,
+    )?;
+    Ok(clone)
+}
+
+fn mul_images(left: &Mat, right: &Mat) -> Result<Mat> {
+    let mut clone = left.clone();
+    multiply(left, right, &mut clone, 1.0, -1)?;
+    Ok(clone)
+}
+
+fn sub_images(left: &Mat, right: &Mat) -> Result<Mat> {
+    let mut clone = left.clone();
+    subtract(left, right, &mut clone, &no_array()?, -1)?;
+    Ok(clone)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image<F: Fn(i32, i32) -> f32>(rows: i32, cols: i32, value: F) -> Result<Mat> {
+        let mut image = Mat::zeros(rows, cols, CV_32F)?.to_mat()?;
+        for i in 0..rows {
+            for j in 0..cols {
+                *image.at_2d_mut::<f32>(i, j)? = value(i, j);
+            }
+        }
+        Ok(image)
+    }
+
+    fn step(_: i32, j: i32) -> f32 {
+        if j < 16 {
+            50.0
+        } else {
+            200.0
+        }
+    }
+
+    fn max_difference<F: Fn(i32, i32) -> f32>(image: &Mat, expected: F) -> Result<f32> {
+        let mut max = 0.0f32;
+        for i in 0..image.rows() {
+            for j in 0..image.cols() {
+                max = max.max((image.at_2d::<f32>(i, j)? - expected(i, j)).abs());
+            }
+        }
+        Ok(max)
+    }
+
+    #[test]
+    fn median_removes_isolated_impulses() -> Result<()> {
+        let noisy = image(32, 32, |i, j| match (i % 7, j % 7) {
+            (3, 3) => 255.0,
+            (5, 1) => 0.0,
+            _ => 100.0,
+        })?;
+        let denoised = denoise(&noisy, &noisy, &Denoise::by_name("median").unwrap())?;
+        assert_eq!(max_difference(&denoised, |_, _| 100.0)?, 0.0);
+        Ok(())
+    }
+
+    #[test]
+    fn median_keeps_a_step_edge() -> Result<()> {
+        let edge = image(32, 32, step)?;
+        let denoised = denoise(&edge, &edge, &Denoise::by_name("median").unwrap())?;
+        assert_eq!(max_difference(&denoised, step)?, 0.0);
+        Ok(())
+    }
+
+    // A ±10 checkerboard on both sides of a 150 step: the bilateral filter
+    // averages the ripple away but hardly mixes the two sides.
+    #[test]
+    fn bilateral_smooths_without_blurring_the_edge() -> Result<()> {
+        let noisy = image(32, 32, |i, j| {
+            step(i, j) + if (i + j) % 2 == 0 { 10.0 } else { -10.0 }
+        })?;
+        let denoised = denoise(&noisy, &noisy, &Denoise::by_name("bilateral").unwrap())?;
+        assert_eq!(max_difference(&noisy, step)?, 10.0);
+        assert!(max_difference(&denoised, step)? < 5.0);
+        Ok(())
+    }
+}
@@ -1,39 +1,73 @@
 use opencv::{
     core::{
-        abs, absdiff, add, bitwise_and, bitwise_not, bitwise_or, convert_scale_abs, min_max_loc,
+        abs, absdiff, add, bitwise_and, bitwise_not, bitwise_or, convert_scale_abs,
         min_max_loc_sparse, no_array, pow, subtract, Mat, Point, Rect, Scalar, BORDER_CONSTANT,
         BORDER_DEFAULT, CV_16SC1, CV_32F, CV_8SC1, CV_8UC1,
     },
     highgui,
-    imgcodecs::{self, IMREAD_GRAYSCALE},
     imgproc::{
-        circle, dilate, erode, flood_fill, laplacian, median_blur, morphology_default_border_value,
-        sobel, threshold, FILLED, THRESH_TOZERO,
+        circle, dilate, erode, flood_fill, laplacian, morphology_default_border_value, sobel,
+        threshold, FILLED, THRESH_TOZERO,
     },
     prelude::*,
     Result,
 };
 
-fn show(name: &str, mat: &Mat) -> Result<()> {
-    highgui::named_window(name, 0)?;
-    highgui::imshow(name, &{
-        let mut clone = mat.clone();
-        mat.convert_to(&mut clone, CV_8UC1, 1.0, 0.0)?;
-        clone
-    })?;
-
-    Ok(())
-}
+#[path = "../../shared/cli.rs"]
+mod cli;
+#[path = "../../shared/color.rs"]
+mod color;
+mod denoise;
+#[path = "../../shared/export.rs"]
+mod export;
+#[path = "../../shared/fft.rs"]
+mod fft;
+#[path = "../../shared/filters.rs"]
+mod filters;
+#[path = "../../shared/image_io.rs"]
+mod image_io;
+#[path = "../../shared/metrics.rs"]
+mod metrics;
+#[path = "../../shared/noise.rs"]
+mod noise;
+#[path = "../../shared/normalization.rs"]
+mod normalization;
+#[path = "../../shared/pipeline.rs"]
+mod pipeline;
+#[path = "../../shared/sharpen.rs"]
+mod sharpen;
+use cli::{arg, arg_choice, arg_value, display_normalization, save};
+use color::{load_color_float, Luminance, LuminanceSpace};
+use denoise::{denoise, Denoise};
+use filters::HighEmphasis;
+use image_io::{load_float, save_float};
+use metrics::{psnr, ssim};
+use noise::{degrade, Noise};
+use normalization::normalize;
+use pipeline::Pipeline;
+use sharpen::{sharpen, Sharpen};
 
 fn main() -> Result<()> {
-    let image_file = {
-        let img = imgcodecs::imread("./skeleton.jpg", IMREAD_GRAYSCALE)?;
-        let mut clone = img.clone();
-        img.convert_to(&mut clone, CV_32F, 1.0, 0.0)?;
-        clone
-    };
-
-    show("image_file", &image_file)?;
+    let image_path = arg("image").unwrap_or_else(|| "./skeleton.jpg".to_string());
+
+    if let Some(seed) = arg_value("benchmark")? {
+        return benchmark_denoise(&load_float(&image_path, 255.0)?, seed);
+    }
+
+    let mut pipeline = Pipeline::new(255.0);
+
+    // With `color=ycrcb|lab|hsv` the chain below runs on the luminance only.
+    let (image_file, color_split) =
+        match arg_choice("color", LuminanceSpace::NAMES, LuminanceSpace::by_name)? {
+            Some(space) => {
+                let image_color = load_color_float(&image_path, 255.0)?;
+                pipeline.stage("image_color", &image_color)?;
+                let color_split = Luminance::split(&image_color, space, 255.0)?;
+                (color_split.luminance.clone(), Some(color_split))
+            }
+            None => (load_float(&image_path, 255.0)?, None),
+        };
+    pipeline.stage("image_file", &image_file)?;
 
     let image_laplacian = {
         let mut clone = image_file.clone();
@@ -41,8 +75,13 @@ fn main() -> Result<()> {
         clone
     };
 
+    // Signed, so it is always written as floats.
+    if let Some(dir) = arg("save") {
+        save_float(&format!("{}/image_laplacian.tiff", dir), &image_laplacian)?;
+    }
+
     let image_laplacian_scaled = &correction(&image_laplacian)?;
-    show("image_laplacian", &image_laplacian_scaled)?;
+    pipeline.stage("image_laplacian", &image_laplacian_scaled)?;
 
     let image_laplacian_sum = {
         let mut clone = image_laplacian.clone();
@@ -56,7 +95,30 @@ fn main() -> Result<()> {
         clone
     };
 
-    show("image_laplacian_sum", &image_laplacian_sum)?;
+    pipeline.stage("image_laplacian_sum", &image_laplacian_sum)?;
+
+    // With `sharpen=unsharp|highboost|laplacian|emphasis` the mask below is
+    // built on the sharpened image instead of the Laplacian sum;
+    // `emphasis=gaussian|butterworth` picks the low-pass of the emphasis.
+    let method = match arg_choice("sharpen", Sharpen::NAMES, Sharpen::by_name)? {
+        Some(Sharpen::HighFrequencyEmphasis { low, high, filter }) => {
+            Some(Sharpen::HighFrequencyEmphasis {
+                low,
+                high,
+                filter: arg_choice("emphasis", HighEmphasis::NAMES, HighEmphasis::by_name)?
+                    .unwrap_or(filter),
+            })
+        }
+        method => method,
+    };
+    let image_sharpened = match method {
+        Some(method) => {
+            let image_sharpened = sharpen(&image_file, &method, 255.0)?;
+            pipeline.stage("image_sharpened", &image_sharpened)?;
+            image_sharpened
+        }
+        None => image_laplacian_sum,
+    };
 
     let image_sobel = {
         let mut clone = image_file.clone();
@@ -94,23 +156,22 @@ fn main() -> Result<()> {
         correction(&abs_image(&clone3)?)?
     };
 
-    show("image_sobel", &image_sobel)?;
+    pipeline.stage("image_sobel", &image_sobel)?;
 
-    let image_median_sobel = {
-        let mut clone = image_sobel.clone();
-        median_blur(&image_sobel, &mut clone, 5)?;
-        clone
-    };
+    let denoise_method = arg_choice("denoise", Denoise::NAMES, Denoise::by_name)?
+        .unwrap_or(Denoise::Median { size: 5 });
 
-    show("image_median_sobel", &image_median_sobel)?;
+    let image_smoothed_sobel = denoise(&image_sobel, &image_file, &denoise_method)?;
+
+    pipeline.stage("image_smoothed_sobel", &image_smoothed_sobel)?;
 
     let image_mask = {
-        image_median_sobel
-            .mul(&image_laplacian_sum, 1.0 / 256.0)?
+        image_smoothed_sobel
+            .mul(&image_sharpened, 1.0 / 256.0)?
             .to_mat()?
     };
 
-    show("image_mask", &image_mask)?;
+    pipeline.stage("image_mask", &image_mask)?;
 
     let image_mask_sum = {
         let mut clone = image_laplacian.clone();
@@ -118,7 +179,7 @@ fn main() -> Result<()> {
         clone
     };
 
-    show("image_mask_sum", &image_mask_sum)?;
+    pipeline.stage("image_mask_sum", &image_mask_sum)?;
 
     let img_mat_sum_pow = {
         let mut image_mask_sum_f = image_mask_sum.clone();
@@ -132,36 +193,96 @@ fn main() -> Result<()> {
         clone2
     };
 
-    show("img_mat_sum_pow", &img_mat_sum_pow)?;
+    pipeline.stage("img_mat_sum_pow", &img_mat_sum_pow)?;
+    save("image_mask_sum", &image_mask_sum, 255.0)?;
+    save("img_mat_sum_pow", &img_mat_sum_pow, 255.0)?;
+
+    if let Some(color_split) = &color_split {
+        pipeline.stage("color_mask_sum", &color_split.merge(&image_mask_sum)?)?;
+        pipeline.stage("color_sum_pow", &color_split.merge(&img_mat_sum_pow)?)?;
+    }
+
+    pipeline.report()?;
+
+    if let Some(path) = arg("export") {
+        pipeline.export(&path)?;
+    }
 
     highgui::wait_key(-1)?;
 
     Ok(())
 }
 
+// Degrades the image with every noise model and scores every denoising
+// method against the clean original.
+fn benchmark_denoise(image: &Mat, seed: u64) -> Result<()> {
+    let models = [
+        Noise::Gaussian { sigma: 20.0 },
+        Noise::Poisson { photons: 30.0 },
+        Noise::SaltAndPepper {
+            amount: 0.05,
+            salt_ratio: 0.5,
+        },
+        Noise::Speckle { sigma: 0.2 },
+        Noise::Periodic {
+            amplitude: 30.0,
+            fx: 0.05,
+            fy: 0.02,
+            phase: 0.0,
+        },
+        Noise::MotionBlur {
+            length: 15.0,
+            angle: 30.0,
+        },
+        Noise::Defocus { radius: 4.0 },
+        Noise::GaussianBlur { sigma: 2.0 },
+    ];
+    for model in &models {
+        let degraded = degrade(image, *model, seed, 255.0)?;
+        println!(
+            "{:?}: noise rms {:.3}, psnr {:.2}, ssim {:.4}",
+            degraded.model,
+            degraded.noise_rms()?,
+            psnr(image, &degraded.image, 255.0)?,
+            ssim(image, &degraded.image, 255.0)?
+        );
+        for name in Denoise::NAMES {
+            let method = Denoise::by_name(name).unwrap();
+            let denoised = denoise(&degraded.image, &degraded.image, &method)?;
+            println!(
+                "    {:<10} psnr {:.2}, ssim {:.4}",
+                name,
+                psnr(image, &denoised, 255.0)?,
+                ssim(image, &denoised, 255.0)?
+            );
+        }
+    }
+    Ok(())
+}
+
+// For the shared modules, which cannot assume whether the getter is fallible.
+fn channels(image: &Mat) -> Result<i32> {
+    image.channels()
+}
+
+fn depth(image: &Mat) -> Result<i32> {
+    image.depth()
+}
+
+fn new_mat() -> Mat {
+    Mat::zeros(0, 0, CV_32F).unwrap().to_mat().unwrap()
+}
+
+fn mul_mat_image(image: &Mat, mul: &Mat) -> Result<Mat> {
+    Ok(image.mul(mul, 1.0)?.to_mat()?)
+}
+
 fn abs_image(image: &Mat) -> Result<Mat> {
     abs(image)?.to_mat()
 }
 
 fn correction(image: &Mat) -> Result<Mat> {
-    let mut min = 0.0;
-    let mut max = 0.0;
-    let mut min_idx = Point::new(0, 0);
-    let mut max_idx = Point::new(0, 0);
-    min_max_loc(
-        image,
-        &mut min,
-        &mut max,
-        &mut min_idx,
-        &mut max_idx,
-        &no_array()?,
-    )?;
-    let mut clone = image.clone();
-    image.convert_to(&mut clone, CV_32F, 1.0, -min)?;
-
-    let mut clone2 = clone.clone();
-    clone.convert_to(&mut clone2, CV_32F, 256.0 / (max - min), 0.0)?;
-    Ok(clone2)
+    Ok(normalize(image, display_normalization()?, 256.0)?.0)
 }
 
 fn flooded_image(image: &Mat, seed: (i32, i32), color: (u8, u8, u8)) -> Result<Mat> {
@@ -0,0 +1,164 @@
+use opencv::{
+    core::{merge, split, Mat},
+    prelude::*,
+    types::VectorOfMat,
+    Result,
+};
+
+use crate::{
+    color::{Luminance, LuminanceSpace},
+    fft::{fft_complex, ifft_complex},
+    filters::{apply_filter, FrequencyFilter},
+    new_mat,
+};
+
+#[derive(Debug, Clone, Copy)]
+pub enum ColorFiltering {
+    // Every channel through its own transform; colour fringes are filtered
+    // like any other detail.
+    PerChannel,
+    // Only the luminance is filtered, the chroma passes unchanged.
+    Luminance(LuminanceSpace),
+}
+
+impl ColorFiltering {
+    pub const NAMES: &'static [&'static str] = &["channels", "ycrcb", "lab", "hsv"];
+
+    pub fn by_name(name: &str) -> Option<ColorFiltering> {
+        match name {
+            "channels" => Some(ColorFiltering::PerChannel),
+            _ => LuminanceSpace::by_name(name).map(ColorFiltering::Luminance),
+        }
+    }
+}
+
+// The spectrum of every channel of `image`, in channel order.
+pub fn channel_spectra(image: &Mat) -> Result<Vec<(Mat, Mat)>> {
+    let mut channels = VectorOfMat::new();
+    split(image, &mut channels)?;
+    channels
+        .iter()
+        .map(|channel| fft_complex(&channel))
+        .collect()
+}
+
+// Filters a single-channel or BGR image in [0, 1] with one filter.
+// Single-channel images go straight through, whatever the mode.
+pub fn filter_color<F: FrequencyFilter + ?Sized>(
+    image: &Mat,
+    mode: ColorFiltering,
+    filter: &F,
+) -> Result<Mat> {
+    let size = image.size()?;
+    let filter_channel =
+        |channel: &Mat| ifft_complex(&apply_filter(&fft_complex(channel)?, filter)?, size);
+    if image.channels()? == 1 {
+        return filter_channel(image);
+    }
+
+    match mode {
+        ColorFiltering::PerChannel => {
+            let mut channels = VectorOfMat::new();
+            split(image, &mut channels)?;
+            let filtered = channels
+                .iter()
+                .map(|channel| filter_channel(&channel))
+                .collect::<Result<Vec<Mat>>>()?;
+            let mut merged = new_mat();
+            merge(&VectorOfMat::from(filtered), &mut merged)?;
+            Ok(merged)
+        }
+        ColorFiltering::Luminance(space) => {
+            let luminance = Luminance::split(image, space, 1.0)?;
+            luminance.merge(&filter_channel(&luminance.luminance)?)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use opencv::{
+        core::{no_array, norm2, CV_32F, NORM_INF},
+        imgproc::{cvt_color, COLOR_BGR2YCrCb},
+    };
+
+    use super::*;
+    use crate::{compose::Constant, filters::Gaussian};
+
+    // Values in [0.2, 0.8], so that no filter below pushes them out of
+    // range; `order` sets how the ramp runs over the pixels.
+    fn ramp(order: i32) -> Result<Mat> {
+        let mut channel = Mat::zeros(16, 16, CV_32F)?.to_mat()?;
+        for i in 0..16 {
+            for j in 0..16 {
+                let step = (i * 16 + j) * order % 17;
+                *channel.at_2d_mut::<f32>(i, j)? = (0.2 + 0.6 * step as f64 / 16.0) as f32;
+            }
+        }
+        Ok(channel)
+    }
+
+    fn bgr(channels: Vec<Mat>) -> Result<Mat> {
+        let mut image = new_mat();
+        merge(&VectorOfMat::from(channels), &mut image)?;
+        Ok(image)
+    }
+
+    fn channels(image: &Mat) -> Result<VectorOfMat> {
+        let mut channels = VectorOfMat::new();
+        split(image, &mut channels)?;
+        Ok(channels)
+    }
+
+    fn max_difference(a: &Mat, b: &Mat) -> Result<f64> {
+        norm2(a, b, NORM_INF, &no_array()?)
+    }
+
+    #[test]
+    fn an_all_pass_filter_keeps_the_image_in_every_mode() -> Result<()> {
+        let image = bgr(vec![ramp(1)?, ramp(3)?, ramp(5)?])?;
+        for name in ColorFiltering::NAMES {
+            let mode = ColorFiltering::by_name(name).unwrap();
+            let filtered = filter_color(&image, mode, &Constant(1.0))?;
+            // Lab goes through gamma tables on the way and back.
+            assert!(max_difference(&image, &filtered)? < 1e-3, "{}", name);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn luminance_filtering_keeps_the_chroma() -> Result<()> {
+        let image = bgr(vec![ramp(1)?, ramp(3)?, ramp(5)?])?;
+        let mode = ColorFiltering::Luminance(LuminanceSpace::YCrCb);
+        let filtered = filter_color(&image, mode, &Gaussian { radius: 3.0 })?;
+        let ycrcb = |image: &Mat| -> Result<VectorOfMat> {
+            let mut converted = new_mat();
+            cvt_color(image, &mut converted, COLOR_BGR2YCrCb, 0)?;
+            channels(&converted)
+        };
+        let (before, after) = (ycrcb(&image)?, ycrcb(&filtered)?);
+        assert!(max_difference(&before.get(0)?, &after.get(0)?)? > 0.01);
+        for c in 1..3 {
+            assert!(max_difference(&before.get(c)?, &after.get(c)?)? < 1e-4);
+        }
+        Ok(())
+    }
+
+    // Y of YCrCb and V of HSV are the grey level itself, so a grey image
+    // comes out of those modes like a single channel. L of Lab is not linear
+    // in the grey level and is left out.
+    #[test]
+    fn grey_images_filter_like_a_single_channel() -> Result<()> {
+        let grey = ramp(1)?;
+        let image = bgr(vec![grey.clone(), grey.clone(), grey.clone()])?;
+        let filter = Gaussian { radius: 3.0 };
+        let expected = filter_color(&grey, ColorFiltering::PerChannel, &filter)?;
+        for name in &["channels", "ycrcb", "hsv"] {
+            let mode = ColorFiltering::by_name(name).unwrap();
+            for channel in channels(&filter_color(&image, mode, &filter)?)?.iter() {
+                assert!(max_difference(&expected, &channel)? < 1e-4, "{}", name);
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,156 @@
+use crate::{filters::FrequencyFilter, shapes::rotate};
+
+// Builds new transfer functions out of existing ones, e.g. a band pass
+// shaped by a low-pass and lifted over a constant floor.
+pub trait Compose: FrequencyFilter + Sized {
+    fn product<F: FrequencyFilter>(self, other: F) -> Product<Self, F> {
+        Product(self, other)
+    }
+
+    fn sum<F: FrequencyFilter>(self, other: F) -> Sum<Self, F> {
+        Sum(self, other)
+    }
+
+    // Moves the centre of the filter to (u0, v0).
+    fn shifted(self, u0: f64, v0: f64) -> Shifted<Self> {
+        Shifted {
+            filter: self,
+            u0,
+            v0,
+        }
+    }
+
+    // Stretches the filter by `su` along u and `sv` along v.
+    fn scaled(self, su: f64, sv: f64) -> Scaled<Self> {
+        Scaled {
+            filter: self,
+            su,
+            sv,
+        }
+    }
+
+    // Rotates the filter by `angle` degrees, from the u axis towards v.
+    fn rotated(self, angle: f64) -> Rotated<Self> {
+        Rotated {
+            filter: self,
+            angle,
+        }
+    }
+}
+
+impl<F: FrequencyFilter> Compose for F {}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Constant(pub f64);
+
+impl FrequencyFilter for Constant {
+    fn response(&self, _u: f64, _v: f64) -> f64 {
+        self.0
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Product<A, B>(pub A, pub B);
+
+impl<A: FrequencyFilter, B: FrequencyFilter> FrequencyFilter for Product<A, B> {
+    fn response(&self, u: f64, v: f64) -> f64 {
+        self.0.response(u, v) * self.1.response(u, v)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Sum<A, B>(pub A, pub B);
+
+impl<A: FrequencyFilter, B: FrequencyFilter> FrequencyFilter for Sum<A, B> {
+    fn response(&self, u: f64, v: f64) -> f64 {
+        self.0.response(u, v) + self.1.response(u, v)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Shifted<F> {
+    pub filter: F,
+    pub u0: f64,
+    pub v0: f64,
+}
+
+impl<F: FrequencyFilter> FrequencyFilter for Shifted<F> {
+    fn response(&self, u: f64, v: f64) -> f64 {
+        self.filter.response(u - self.u0, v - self.v0)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Scaled<F> {
+    pub filter: F,
+    pub su: f64,
+    pub sv: f64,
+}
+
+impl<F: FrequencyFilter> FrequencyFilter for Scaled<F> {
+    fn response(&self, u: f64, v: f64) -> f64 {
+        self.filter.response(u / self.su, v / self.sv)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Rotated<F> {
+    pub filter: F,
+    pub angle: f64,
+}
+
+impl<F: FrequencyFilter> FrequencyFilter for Rotated<F> {
+    fn response(&self, u: f64, v: f64) -> f64 {
+        let (u, v) = rotate(u, v, -self.angle);
+        self.filter.response(u, v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filters::{Butterworth, Gaussian};
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn products_and_sums_combine_the_responses() {
+        let gaussian = Gaussian { radius: 10.0 };
+        let butterworth = Butterworth { radius: 20.0, n: 1 };
+        for &(u, v) in &[(0.0, 0.0), (5.0, -3.0), (12.0, 20.0)] {
+            let (g, b) = (gaussian.response(u, v), butterworth.response(u, v));
+            assert!(close(gaussian.product(butterworth).response(u, v), g * b));
+            assert!(close(gaussian.sum(butterworth).response(u, v), g + b));
+            assert!(close(gaussian.sum(Constant(0.2)).response(u, v), g + 0.2));
+        }
+    }
+
+    // A Gaussian falls to exp(-1/2) at its radius; every transform has to
+    // move that cutoff to where it says.
+    #[test]
+    fn shifts_scales_and_rotations_move_the_cutoff() {
+        let gaussian = Gaussian { radius: 10.0 };
+        let edge = (-0.5f64).exp();
+
+        let shifted = gaussian.shifted(30.0, -20.0);
+        assert!(close(shifted.response(30.0, -20.0), 1.0));
+        assert!(close(shifted.response(40.0, -20.0), edge));
+        assert!(close(shifted.response(30.0, -30.0), edge));
+
+        let scaled = gaussian.scaled(2.0, 0.5);
+        assert!(close(scaled.response(20.0, 0.0), edge));
+        assert!(close(scaled.response(0.0, 5.0), edge));
+
+        let turned = scaled.rotated(90.0);
+        assert!(close(turned.response(0.0, 20.0), edge));
+        assert!(close(turned.response(5.0, 0.0), edge));
+
+        let oblique = scaled.rotated(30.0);
+        let (u, v) = rotate(20.0, 0.0, 30.0);
+        assert!(close(oblique.response(u, v), edge));
+        let (u, v) = rotate(0.0, 5.0, 30.0);
+        assert!(close(oblique.response(u, v), edge));
+    }
+}
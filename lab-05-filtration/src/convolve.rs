@@ -0,0 +1,47 @@
+use opencv::{
+    core::{self, Mat},
+    prelude::*,
+    Error, Result,
+};
+
+use crate::{
+    fast_fft::FastFft,
+    filters::FrequencyFilter,
+    kernel::{Border, Kernel, Method},
+};
+
+// A kernel works in every method; a transfer function only exists in the
+// frequency domain.
+pub enum Operator<'a> {
+    Kernel(&'a Kernel),
+    Transfer(&'a dyn FrequencyFilter),
+}
+
+// True convolution with the anchor in the kernel centre, by `method` or the
+// one `Kernel::convolve` picks; every method sees the same `border`.
+// Transfer functions are applied to the cyclically extended image through
+// `fast_fft`, so its plans carry over between calls and `border` does not
+// apply.
+pub fn convolve(
+    image: &Mat,
+    operator: Operator,
+    border: Border,
+    method: Option<Method>,
+    fast_fft: &mut FastFft,
+) -> Result<Mat> {
+    match operator {
+        Operator::Transfer(filter) => {
+            if method.unwrap_or(Method::Fourier) != Method::Fourier {
+                return Err(Error::new(
+                    core::StsBadArg,
+                    "a transfer function can only be applied through the DFT".to_string(),
+                ));
+            }
+            fast_fft.filter(image, filter)
+        }
+        Operator::Kernel(kernel) => match method {
+            Some(method) => kernel.convolve_by(image, border, method),
+            None => kernel.convolve(image, border),
+        },
+    }
+}
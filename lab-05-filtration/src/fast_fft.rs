@@ -0,0 +1,262 @@
+use std::collections::{hash_map::Entry, HashMap};
+
+use opencv::{
+    core::{
+        self, dft, get_optimal_dft_size, idft, Mat, Rect, Size, CV_32F, DFT_REAL_OUTPUT, DFT_SCALE,
+    },
+    prelude::*,
+    Error, Result,
+};
+
+use crate::{filters::FrequencyFilter, mul_mat_image, new_mat};
+
+// Spectrum of a real image in OpenCV's packed CCS layout: unshifted, one
+// float channel holding only the non-redundant half. `size` is the size of the
+// image before padding.
+pub struct PackedSpectrum {
+    pub data: Mat,
+    pub size: Size,
+}
+
+// Everything that depends only on the image size: a zeroed input buffer of
+// the padded size, of which only the image area is ever written so the
+// padding stays zero, and the gain map of every filter applied at that size,
+// keyed by the filter's `Debug` form.
+struct Plan {
+    input: Mat,
+    gains: HashMap<String, Mat>,
+}
+
+// Real-input transforms without the merge, split and shift of `fft_complex`.
+// Plans are kept per image size, so repeated filtering of equally sized
+// images only pays for the transforms themselves. Gain maps are never
+// evicted; use a fresh `FastFft` for a stream of one-off filters.
+#[derive(Default)]
+pub struct FastFft {
+    plans: HashMap<(i32, i32), Plan>,
+}
+
+impl FastFft {
+    pub fn new() -> FastFft {
+        FastFft {
+            plans: HashMap::new(),
+        }
+    }
+
+    fn plan(&mut self, size: Size) -> Result<&mut Plan> {
+        match self.plans.entry((size.width, size.height)) {
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+            Entry::Vacant(entry) => {
+                let input = Mat::zeros(
+                    get_optimal_dft_size(size.height)?,
+                    get_optimal_dft_size(size.width)?,
+                    CV_32F,
+                )?
+                .to_mat()?;
+                Ok(entry.insert(Plan {
+                    input,
+                    gains: HashMap::new(),
+                }))
+            }
+        }
+    }
+
+    // Any single-channel depth; the image is converted to CV_32F on its way
+    // into the input buffer.
+    pub fn forward(&mut self, image: &Mat) -> Result<PackedSpectrum> {
+        if image.channels()? != 1 {
+            return Err(Error::new(
+                core::StsBadArg,
+                format!(
+                    "the real-input transform needs one channel, got {}",
+                    image.channels()?
+                ),
+            ));
+        }
+        let size = image.size()?;
+        let plan = self.plan(size)?;
+        let mut target = Mat::roi(&plan.input, Rect::new(0, 0, size.width, size.height))?;
+        image.convert_to(&mut target, CV_32F, 1.0, 0.0)?;
+
+        let mut data = new_mat();
+        dft(&plan.input, &mut data, 0, size.height)?;
+        Ok(PackedSpectrum { data, size })
+    }
+
+    pub fn inverse(&self, spectrum: &PackedSpectrum) -> Result<Mat> {
+        let mut result = new_mat();
+        idft(&spectrum.data, &mut result, DFT_SCALE | DFT_REAL_OUTPUT, 0)?;
+        let size = spectrum.size;
+        Ok(Mat::roi(&result, Rect::new(0, 0, size.width, size.height))?.clone())
+    }
+
+    pub fn filter<F: FrequencyFilter + ?Sized>(&mut self, image: &Mat, filter: &F) -> Result<Mat> {
+        let spectrum = self.forward(image)?;
+        let padded = spectrum.data.size()?;
+        let plan = self.plan(spectrum.size)?;
+        let gain = match plan.gains.entry(format!("{:?}", filter)) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(gain_map(filter, padded)?),
+        };
+        let filtered = apply_gain(&spectrum, gain)?;
+        self.inverse(&filtered)
+    }
+}
+
+// The filter sampled in the CCS layout, with the same gain on the real and
+// imaginary part of every frequency, so that filtering is a plain per-element
+// product. The transfer function is assumed symmetric, H(u, v) = H(-u, -v),
+// as it must be for the filtered image to stay real.
+pub fn gain_map<F: FrequencyFilter + ?Sized>(filter: &F, padded: Size) -> Result<Mat> {
+    let (rows, cols) = (padded.height, padded.width);
+    let width = cols as usize;
+    // Offsets from the zero frequency, matching the layout `fft_shift` gives.
+    let frequency = |index: i32, len: i32| {
+        if index < (len + 1) / 2 {
+            index as f64
+        } else {
+            (index - len) as f64
+        }
+    };
+
+    let mut gain = Mat::zeros(rows, cols, CV_32F)?.to_mat()?;
+    let values = gain.data_typed_mut::<f32>()?;
+
+    // Inner columns hold Re, Im pairs of Y(m, j) for j = 1 ..= (cols - 1) / 2.
+    for m in 0..rows {
+        let v = frequency(m, rows);
+        let row = &mut values[m as usize * width..(m as usize + 1) * width];
+        for j in 1..=(cols - 1) / 2 {
+            let h = filter.response(j as f64, v) as f32;
+            row[2 * j as usize - 1] = h;
+            row[2 * j as usize] = h;
+        }
+    }
+
+    // The first column, and the last one for even widths, pack Y(k, 0) and
+    // Y(k, cols / 2) down the rows in the same Re, Im fashion.
+    let mut edges = vec![(0, 0.0)];
+    if cols % 2 == 0 && cols > 1 {
+        edges.push((width - 1, frequency(cols / 2, cols)));
+    }
+    for (column, u) in edges {
+        values[column] = filter.response(u, 0.0) as f32;
+        for k in 1..=(rows - 1) / 2 {
+            let h = filter.response(u, k as f64) as f32;
+            values[(2 * k as usize - 1) * width + column] = h;
+            values[2 * k as usize * width + column] = h;
+        }
+        if rows % 2 == 0 && rows > 1 {
+            values[(rows as usize - 1) * width + column] =
+                filter.response(u, frequency(rows / 2, rows)) as f32;
+        }
+    }
+    Ok(gain)
+}
+
+pub fn apply_gain(spectrum: &PackedSpectrum, gain: &Mat) -> Result<PackedSpectrum> {
+    Ok(PackedSpectrum {
+        data: mul_mat_image(&spectrum.data, gain)?,
+        size: spectrum.size,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use opencv::{
+        core::{no_array, norm2, CV_8U, NORM_INF},
+        imgproc::{cvt_color, COLOR_GRAY2BGR},
+    };
+
+    use super::*;
+    use crate::{
+        fft_complex,
+        filters::{apply_filter, Gaussian},
+        ifft_complex,
+        shapes::{Elliptic, Profile},
+    };
+
+    fn noise(rows: i32, cols: i32) -> Result<Mat> {
+        let mut image = Mat::zeros(rows, cols, CV_32F)?.to_mat()?;
+        let mut state = 7u32;
+        for value in image.data_typed_mut::<f32>()? {
+            state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            *value = (state >> 8) as f32 / (1u32 << 24) as f32;
+        }
+        Ok(image)
+    }
+
+    // Odd and even padded sizes exercise both layouts of the CCS edge
+    // columns and rows; the two filters share one plan, so the second must
+    // not pick up the gain map of the first.
+    #[test]
+    fn gain_map_matches_the_complex_path() -> Result<()> {
+        let elliptic = Elliptic {
+            radius_u: 6.0,
+            radius_v: 3.0,
+            angle: 30.0,
+            profile: Profile::Gaussian,
+        };
+        let gaussian = Gaussian { radius: 4.0 };
+        for &(rows, cols) in &[(27, 45), (36, 50), (45, 32)] {
+            let image = noise(rows, cols)?;
+            let mut fast_fft = FastFft::new();
+            for filter in &[&elliptic as &dyn FrequencyFilter, &gaussian] {
+                let expected =
+                    ifft_complex(&apply_filter(&fft_complex(&image)?, filter)?, image.size()?)?;
+                for _ in 0..2 {
+                    let filtered = fast_fft.filter(&image, filter)?;
+                    let difference = norm2(&expected, &filtered, NORM_INF, &no_array()?)?;
+                    assert!(
+                        difference < 1e-4,
+                        "{}x{} {:?}: {}",
+                        cols,
+                        rows,
+                        filter,
+                        difference
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn forward_converts_other_depths() -> Result<()> {
+        let image = noise(20, 30)?;
+        let mut image_8u = new_mat();
+        image.convert_to(&mut image_8u, CV_8U, 255.0, 0.0)?;
+        let mut image_32f = new_mat();
+        image_8u.convert_to(&mut image_32f, CV_32F, 1.0, 0.0)?;
+
+        let mut fast_fft = FastFft::new();
+        let from_8u = fast_fft.forward(&image_8u)?.data;
+        let from_32f = fast_fft.forward(&image_32f)?.data;
+        assert_eq!(norm2(&from_8u, &from_32f, NORM_INF, &no_array()?)?, 0.0);
+
+        let mut color = new_mat();
+        cvt_color(&image, &mut color, COLOR_GRAY2BGR, 0)?;
+        assert!(fast_fft.forward(&color).is_err());
+        Ok(())
+    }
+
+    // The target of the fast path. Ignored because its timing depends on the
+    // machine and a debug build is far slower; run it with
+    // `cargo test --release -- --ignored`.
+    #[test]
+    #[ignore]
+    fn filters_4096_squared_within_a_second() -> Result<()> {
+        let image = noise(4096, 4096)?;
+        let filter = Gaussian { radius: 30.0 };
+        let mut fast_fft = FastFft::new();
+        fast_fft.filter(&image, &filter)?;
+
+        let start = Instant::now();
+        fast_fft.filter(&image, &filter)?;
+        let elapsed = start.elapsed();
+        assert!(elapsed.as_secs_f64() < 1.0, "took {:?}", elapsed);
+        Ok(())
+    }
+}
@@ -1,36 +1,94 @@
+use std::time::Instant;
+
 use opencv::{
-    core::{
-        dft, idft, log, magnitude, merge, min_max_loc, no_array, split, Mat, Point, Rect, Scalar,
-        CV_32F, CV_8UC1, DFT_COMPLEX_INPUT, DFT_COMPLEX_OUTPUT, DFT_REAL_OUTPUT, DFT_SCALE,
-    },
+    core::{log, magnitude, Mat, Size, CV_32F},
     highgui,
-    imgcodecs::{self, IMREAD_GRAYSCALE},
-    imgproc::flood_fill,
     prelude::*,
-    types::VectorOfMat,
     Result,
 };
 
+#[path = "../../shared/cli.rs"]
+mod cli;
+#[path = "../../shared/color.rs"]
+mod color;
+mod color_filtering;
+mod compose;
+mod convolve;
+#[path = "../../shared/export.rs"]
+mod export;
+mod fast_fft;
+#[path = "../../shared/fft.rs"]
+mod fft;
+#[path = "../../shared/filters.rs"]
 mod filters;
-use filters::{apply_filter, butterworth_filter, gaussian_filter, perfect_filter, rev};
-
-fn show(name: &str, mat: &Mat) -> Result<()> {
-    highgui::named_window(name, 0)?;
-    highgui::imshow(name, &{
-        let mut clone = mat.clone();
-        mat.convert_to(&mut clone, CV_8UC1, 255.0, 0.0)?;
-        clone
-    })?;
+#[path = "../../shared/homomorphic.rs"]
+mod homomorphic;
+#[path = "../../shared/image_io.rs"]
+mod image_io;
+#[path = "../../shared/kernel.rs"]
+mod kernel;
+mod matching;
+#[path = "../../shared/metrics.rs"]
+mod metrics;
+#[path = "../../shared/noise.rs"]
+mod noise;
+#[path = "../../shared/normalization.rs"]
+mod normalization;
+mod notch;
+#[path = "../../shared/pipeline.rs"]
+mod pipeline;
+mod registration;
+mod restoration;
+mod shapes;
+#[path = "../../shared/sharpen.rs"]
+mod sharpen;
+mod spectrum;
+mod window;
+use cli::{arg, arg_choice, arg_value, display_normalization, save};
+use color::load_color_float;
+use color_filtering::{channel_spectra, filter_color, ColorFiltering};
+use compose::{Compose, Constant};
+use convolve::{convolve, Operator};
+use fast_fft::FastFft;
+use fft::{fft_complex, fft_complex_unpadded, fft_shift, ifft_complex, roll};
+use filters::{apply_filter, Butterworth, FrequencyFilter, Gaussian, HighEmphasis};
+use homomorphic::{homomorphic, Homomorphic};
+use image_io::{load_float, save_float};
+use kernel::{Border, Kernel};
+use matching::{best_matches, match_template, Execution, MatchScore};
+use metrics::{psnr, ssim, tenengrad, variance_of_laplacian};
+use noise::{degrade, Noise};
+use normalization::{normalize, Mapping};
+use notch::{auto_notch_reject, PeakSearch};
+use pipeline::Pipeline;
+use registration::{log_polar_registration, phase_correlation};
+use restoration::{restore, Restoration};
+use shapes::{ButterworthBand, Elliptic, Gabor, GaussianBand, Ideal, IdealBand, Profile, Wedge};
+use sharpen::{sharpen, Sharpen};
+use spectrum::{
+    annotate_axes, color_mapped, log_magnitude, log_spectrum, phase_wheel, plot_radial_power,
+    radial_power_spectrum, ColorMap,
+};
+use window::{apodize, filter_apodized, periodic_smooth, Apodization, Window};
 
-    Ok(())
+// For images that are already 8-bit BGR; they are staged in [0, 1] like
+// the rest.
+fn stage_bgr(pipeline: &mut Pipeline, name: &str, mat: &Mat) -> Result<()> {
+    pipeline.stage(name, &mul_image(mat, 1.0 / 255.0)?)
 }
 
-fn show_filter(name: &str, fft: &(Mat, Mat), filter: &Mat) -> Result<()> {
-    let image_filter = filter;
-    show(&format!("image {} filter", name), &image_filter)?;
+fn stage_filter<F: FrequencyFilter + ?Sized>(
+    pipeline: &mut Pipeline,
+    name: &str,
+    fft: &(Mat, Mat),
+    size: Size,
+    filter: &F,
+) -> Result<()> {
+    let image_filter = filter.rasterize(fft.0.size()?)?;
+    pipeline.stage(&format!("image {} filter", name), &image_filter)?;
 
-    let image_filtered = ifft_complex(&apply_filter(&fft, &image_filter)?)?;
-    show(&format!("image {} filtered", name), &image_filtered)?;
+    let image_filtered = ifft_complex(&apply_filter(&fft, filter)?, size)?;
+    pipeline.stage(&format!("image {} filtered", name), &image_filtered)?;
 
     // show(
     //     &format!("image {} filtered spectrum", name),
@@ -40,119 +98,521 @@ fn show_filter(name: &str, fft: &(Mat, Mat), filter: &Mat) -> Result<()> {
 }
 
 fn main() -> Result<()> {
-    let image_file = {
-        let img = imgcodecs::imread("./example.png", IMREAD_GRAYSCALE)?;
-        let mut clone = img.clone();
-        img.convert_to(&mut clone, CV_32F, 1.0 / 255.0, 0.0)?;
-        clone
-    };
+    let image_path = arg("image").unwrap_or_else(|| "./example.png".to_string());
+    let image_file = load_float(&image_path, 1.0)?;
 
-    show("image file", &image_file)?;
+    // `benchmark=fft` times the packed real FFT against the complex one,
+    // `benchmark=<seed>` scores the low-pass filters on degraded copies.
+    if arg("benchmark").as_deref() == Some("fft") {
+        return benchmark_fft(&image_file);
+    }
+    if let Some(seed) = arg_value("benchmark")? {
+        return benchmark_low_pass(&image_file, seed);
+    }
+
+    let mut pipeline = Pipeline::new(1.0);
+    pipeline.stage("image file", &image_file)?;
 
     let fft = fft_complex(&image_file)?;
 
-    show("image magnitude", &fft_magnitude(&fft)?)?;
-    show("image magnitude_log", &fft_magnitude_log(&fft)?)?;
+    if let Some(dir) = arg("save") {
+        save_float(&format!("{}/spectrum_re.tiff", dir), &fft.0)?;
+        save_float(&format!("{}/spectrum_im.tiff", dir), &fft.1)?;
+    }
+
+    let (image_magnitude, magnitude_mapping) = fft_magnitude(&fft)?;
+    println!("magnitude normalisation: {}", magnitude_mapping);
+    pipeline.stage("image magnitude", &image_magnitude)?;
+
+    let (image_magnitude_log, magnitude_log_mapping) = fft_magnitude_log(&fft)?;
+    println!("log magnitude normalisation: {}", magnitude_log_mapping);
+    pipeline.stage("image magnitude_log", &image_magnitude_log)?;
+
+    if let Some(window) = arg_choice("window", Window::NAMES, Window::by_name)? {
+        let (image_windowed_log, _) =
+            fft_magnitude_log(&fft_complex(&apodize(&image_file, window)?)?)?;
+        pipeline.stage("image windowed magnitude_log", &image_windowed_log)?;
+        let filter = Gaussian { radius: 30.0 };
+        for (name, apodization) in &[
+            ("plain", Apodization::None),
+            ("windowed", Apodization::Window(window)),
+            ("periodic", Apodization::PeriodicSmooth),
+        ] {
+            pipeline.stage(
+                &format!("image {} gaussian", name),
+                &filter_apodized(&image_file, *apodization, &filter, 0.05)?,
+            )?;
+        }
+    }
+    let (image_periodic_log, _) =
+        fft_magnitude_log(&fft_complex_unpadded(&periodic_smooth(&image_file)?.0)?)?;
+    pipeline.stage(
+        "image periodic component magnitude_log",
+        &image_periodic_log,
+    )?;
 
-    show_filter("perfect", &fft, &perfect_filter(&image_file, 30)?)?;
-    show_filter(
-        "butterworth",
-        &fft,
-        &butterworth_filter(&image_file, 30, 1)?,
+    let color_map =
+        arg_choice("colormap", ColorMap::NAMES, ColorMap::by_name)?.unwrap_or(ColorMap::Viridis);
+    stage_bgr(
+        &mut pipeline,
+        "image spectrum",
+        &annotate_axes(&color_mapped(&log_magnitude(&fft)?, color_map)?, 0.1)?,
+    )?;
+    stage_bgr(
+        &mut pipeline,
+        "image phase",
+        &annotate_axes(&phase_wheel(&fft)?, 0.1)?,
     )?;
-    show_filter("gaussian", &fft, &gaussian_filter(&image_file, 30)?)?;
 
-    show_filter(
-        "rev perfect",
+    // With `color=channels|ycrcb|lab|hsv` the colour image is filtered too.
+    if let Some(mode) = arg_choice("color", ColorFiltering::NAMES, ColorFiltering::by_name)? {
+        let image_color = load_color_float(&image_path, 1.0)?;
+        pipeline.stage("image color", &image_color)?;
+        for (name, spectrum) in ["blue", "green", "red"]
+            .iter()
+            .zip(channel_spectra(&image_color)?)
+        {
+            stage_bgr(
+                &mut pipeline,
+                &format!("image {} spectrum", name),
+                &annotate_axes(&color_mapped(&log_magnitude(&spectrum)?, color_map)?, 0.1)?,
+            )?;
+        }
+        let image_color_filtered = filter_color(&image_color, mode, &Gaussian { radius: 30.0 })?;
+        pipeline.stage("image color gaussian filtered", &image_color_filtered)?;
+        println!(
+            "image color gaussian filtered ({:?}): psnr {:.2}, ssim {:.4}",
+            mode,
+            psnr(&image_color, &image_color_filtered, 1.0)?,
+            ssim(&image_color, &image_color_filtered, 1.0)?,
+        );
+    }
+    stage_bgr(
+        &mut pipeline,
+        "image radial power",
+        &plot_radial_power(&radial_power_spectrum(&fft, 128)?, 512, 256)?,
+    )?;
+
+    let size = image_file.size()?;
+    let mut fast_fft = FastFft::new();
+
+    let kernel = match arg("kernel") {
+        Some(path) => Kernel::load(&path)?,
+        None => Kernel::laplacian(),
+    };
+    let border = arg_choice("border", Border::NAMES, Border::by_name)?.unwrap_or(Border::Reflect);
+    // A large disk is not separable, so it goes through the DFT.
+    let disk = Kernel::new(
+        31,
+        31,
+        (0..31 * 31)
+            .map(|i| {
+                let (y, x) = ((i / 31) as f64 - 15.0, (i % 31) as f64 - 15.0);
+                if x.hypot(y) <= 15.0 {
+                    1.0
+                } else {
+                    0.0
+                }
+            })
+            .collect(),
+    )?
+    .normalized();
+    for (name, kernel) in &[("kernel", &kernel), ("disk", &disk)] {
+        let start = Instant::now();
+        let image_convolved = convolve(
+            &image_file,
+            Operator::Kernel(kernel),
+            border,
+            None,
+            &mut fast_fft,
+        )?;
+        println!(
+            "{} convolution: {:?} in {:?}",
+            name,
+            kernel.method_for(&image_file)?,
+            start.elapsed()
+        );
+        pipeline.stage(&format!("image {} convolved", name), &image_convolved)?;
+    }
+
+    pipeline.stage(
+        "image gaussian transfer convolved",
+        &convolve(
+            &image_file,
+            Operator::Transfer(&Gaussian { radius: 30.0 }),
+            border,
+            None,
+            &mut fast_fft,
+        )?,
+    )?;
+
+    let low_pass: [(&str, Box<dyn FrequencyFilter>); 3] = [
+        ("perfect", Box::new(Ideal { radius: 30.0 })),
+        ("butterworth", Box::new(Butterworth { radius: 30.0, n: 1 })),
+        ("gaussian", Box::new(Gaussian { radius: 30.0 })),
+    ];
+    for (name, filter) in &low_pass {
+        stage_filter(&mut pipeline, name, &fft, size, filter)?;
+    }
+    for (name, filter) in &low_pass {
+        stage_filter(
+            &mut pipeline,
+            &format!("rev {}", name),
+            &fft,
+            size,
+            &filter.complement(),
+        )?;
+    }
+
+    let band_pass: [(&str, Box<dyn FrequencyFilter>); 3] = [
+        (
+            "ideal",
+            Box::new(IdealBand {
+                center: 40.0,
+                width: 20.0,
+            }),
+        ),
+        (
+            "butterworth",
+            Box::new(ButterworthBand {
+                center: 40.0,
+                width: 20.0,
+                n: 2,
+            }),
+        ),
+        (
+            "gaussian",
+            Box::new(GaussianBand {
+                center: 40.0,
+                width: 20.0,
+            }),
+        ),
+    ];
+    for (name, filter) in &band_pass {
+        stage_filter(
+            &mut pipeline,
+            &format!("{} band pass", name),
+            &fft,
+            size,
+            filter,
+        )?;
+    }
+    for (name, filter) in &band_pass {
+        stage_filter(
+            &mut pipeline,
+            &format!("{} band reject", name),
+            &fft,
+            size,
+            &filter.complement(),
+        )?;
+    }
+
+    stage_filter(
+        &mut pipeline,
+        "oriented anisotropic gaussian",
         &fft,
-        &rev(&perfect_filter(&image_file, 30)?)?,
+        size,
+        &Gaussian { radius: 30.0 }.scaled(2.0, 0.5).rotated(30.0),
     )?;
-    show_filter(
-        "rev butterworth",
+    stage_filter(
+        &mut pipeline,
+        "gaussian pair",
         &fft,
-        &rev(&butterworth_filter(&image_file, 30, 1)?)?,
+        size,
+        &Gaussian { radius: 10.0 }
+            .shifted(40.0, 0.0)
+            .sum(Gaussian { radius: 10.0 }.shifted(-40.0, 0.0)),
     )?;
-    show_filter(
-        "rev gaussian",
+    stage_filter(
+        &mut pipeline,
+        "gaussian band over constant",
         &fft,
-        &rev(&gaussian_filter(&image_file, 30)?)?,
+        size,
+        &GaussianBand {
+            center: 40.0,
+            width: 20.0,
+        }
+        .product(Gaussian { radius: 60.0 })
+        .sum(Constant(0.2)),
+    )?;
+
+    let oriented: [(&str, Box<dyn FrequencyFilter>); 3] = [
+        (
+            "elliptic",
+            Box::new(Elliptic {
+                radius_u: 60.0,
+                radius_v: 15.0,
+                angle: 30.0,
+                profile: Profile::Butterworth { n: 2 },
+            }),
+        ),
+        (
+            "wedge",
+            Box::new(Wedge {
+                angle: 0.0,
+                width: 30.0,
+                softness: 10.0,
+            }),
+        ),
+        (
+            "gabor",
+            Box::new(Gabor {
+                frequency: 40.0,
+                angle: 45.0,
+                sigma_radial: 10.0,
+                sigma_angular: 5.0,
+            }),
+        ),
+    ];
+    for (name, filter) in &oriented {
+        stage_filter(&mut pipeline, name, &fft, size, filter)?;
+    }
+
+    for name in Sharpen::NAMES {
+        let method = Sharpen::by_name(name).unwrap();
+        let image_sharpened = sharpen(&image_file, &method, 1.0)?;
+        pipeline.stage(&format!("image {} sharpened", name), &image_sharpened)?;
+        println!(
+            "image {} sharpened: var(lap) {:.4}, tenengrad {:.4}",
+            name,
+            variance_of_laplacian(&image_sharpened)?,
+            tenengrad(&image_sharpened)?,
+        );
+    }
+
+    let image_periodic = degrade(
+        &image_file,
+        Noise::Periodic {
+            amplitude: 0.1,
+            fx: 0.05,
+            fy: 0.02,
+            phase: 0.0,
+        },
+        0,
+        1.0,
+    )?
+    .image;
+    pipeline.stage("image periodic", &image_periodic)?;
+    let fft_periodic = fft_complex(&image_periodic)?;
+    // `notch=ideal|butterworth|gaussian` shapes the notches; `notch_k=` and
+    // `notch_peaks=` set how far above the background a spike has to stand
+    // and how many are suppressed.
+    let notch_radius = 4.0;
+    let notch_filter = auto_notch_reject(
+        &fft_periodic,
+        notch_radius,
+        arg_choice("notch", Profile::NAMES, Profile::by_name)?.unwrap_or(Profile::Gaussian),
+        &PeakSearch {
+            exclude_radius: 3.0 * notch_radius,
+            k: arg_value("notch_k")?.unwrap_or(4.0),
+            max_peaks: arg_value("notch_peaks")?.unwrap_or(8),
+        },
+    )?;
+    println!("notch peaks: {:?}", notch_filter.notches);
+    stage_filter(
+        &mut pipeline,
+        "auto notch",
+        &fft_periodic,
+        size,
+        &notch_filter,
     )?;
+    let image_notched = ifft_complex(&apply_filter(&fft_periodic, &notch_filter)?, size)?;
+    println!(
+        "image auto notch against clean: psnr {:.2}, ssim {:.4}",
+        psnr(&image_file, &image_notched, 1.0)?,
+        ssim(&image_file, &image_notched, 1.0)?,
+    );
+
+    for name in HighEmphasis::NAMES {
+        let image_homomorphic = homomorphic(
+            &image_file,
+            &Homomorphic {
+                filter: HighEmphasis::by_name(name).unwrap(),
+                low: 0.5,
+                high: 1.5,
+            },
+        )?;
+        pipeline.stage(&format!("image {} homomorphic", name), &image_homomorphic)?;
+        // Floats by default, so that the thresholding labs can load it.
+        save(&format!("homomorphic_{}", name), &image_homomorphic, 1.0)?;
+    }
+
+    for (blur_name, model) in &[
+        (
+            "motion",
+            Noise::MotionBlur {
+                length: 15.0,
+                angle: 30.0,
+            },
+        ),
+        ("gaussian", Noise::GaussianBlur { sigma: 2.0 }),
+    ] {
+        let blurred = degrade(&image_file, *model, 0, 1.0)?;
+        let psf = match &blurred.psf {
+            Some(psf) => psf,
+            None => continue,
+        };
+        pipeline.stage(&format!("image {} blurred", blur_name), &blurred.image)?;
+        for (name, method) in &[
+            ("inverse", Restoration::Inverse { threshold: 0.1 }),
+            ("wiener", Restoration::Wiener { nsr: 0.01 }),
+            ("cls", Restoration::ConstrainedLeastSquares { gamma: 0.01 }),
+            (
+                "richardson-lucy",
+                Restoration::RichardsonLucy { iterations: 30 },
+            ),
+        ] {
+            let image_restored = restore(&blurred.image, psf, method)?;
+            let stage = format!("image {} {} restored", blur_name, name);
+            pipeline.stage(&stage, &image_restored)?;
+            println!(
+                "{}: psnr {:.2}, ssim {:.4}",
+                stage,
+                psnr(&image_file, &image_restored, 1.0)?,
+                ssim(&image_file, &image_restored, 1.0)?,
+            );
+        }
+    }
+
+    let image_shifted = roll(&image_file, 12, -7)?;
+    println!(
+        "phase correlation: {:?}",
+        phase_correlation(&image_file, &image_shifted)?
+    );
+    if let Some(path) = arg("register") {
+        let image_other = load_float(&path, 1.0)?;
+        println!(
+            "log-polar registration: {:?}",
+            log_polar_registration(&image_file, &image_other)?
+        );
+    }
+
+    if let Some(path) = arg("template") {
+        let template = load_float(&path, 1.0)?;
+        for score in &[
+            MatchScore::Ssd,
+            MatchScore::CrossCorrelation,
+            MatchScore::Zncc,
+        ] {
+            let scores = match_template(
+                &image_file,
+                &template,
+                *score,
+                Execution::Auto { min_area: 32 * 32 },
+            )?;
+            pipeline.stage(&format!("image {:?} scores", score), &correction(&scores)?)?;
+            println!(
+                "{:?} matches: {:?}",
+                score,
+                best_matches(&scores, *score, template.size()?, 5)?
+            );
+        }
+    }
+    pipeline.report()?;
+
+    if let Some(path) = arg("export") {
+        pipeline.export(&path)?;
+    }
     highgui::wait_key(-1)?;
 
     Ok(())
 }
 
-fn ifft_complex(fft: &(Mat, Mat)) -> Result<Mat> {
-    let vec_of_mat =
-        VectorOfMat::from(vec![fft_shift(&fft.0.clone())?, fft_shift(&fft.1.clone())?]);
-    let mut image_complex = new_mat();
-    merge(&vec_of_mat, &mut image_complex)?;
-
-    let mut result = new_mat();
-    idft(&image_complex, &mut result, DFT_REAL_OUTPUT, 0)?;
-    Ok(result)
+// Filters the image through the packed real FFT and the complex one. The
+// second run reuses the plan and gain map of the first.
+fn benchmark_fft(image: &Mat) -> Result<()> {
+    let size = image.size()?;
+    let mut fast_fft = FastFft::new();
+    let filter = Gaussian { radius: 30.0 };
+    for _ in 0..2 {
+        let start = Instant::now();
+        let image_fast = fast_fft.filter(image, &filter)?;
+        let fast = start.elapsed();
+        let start = Instant::now();
+        let image_complex = ifft_complex(&apply_filter(&fft_complex(image)?, &filter)?, size)?;
+        println!(
+            "gaussian filter: fast path {:?}, complex path {:?}, psnr between them {:.2}",
+            fast,
+            start.elapsed(),
+            psnr(&image_complex, &image_fast, 1.0)?
+        );
+    }
+    Ok(())
 }
 
-fn fft_complex(image: &Mat) -> Result<(Mat, Mat)> {
-    let vec_of_mat = VectorOfMat::from(vec![
-        image.clone(),
-        Mat::zeros(image.rows(), image.cols(), CV_32F)?.to_mat()?,
-    ]);
-    let mut image_complex = new_mat();
-    merge(&vec_of_mat, &mut image_complex)?;
-
-    let mut image_dft = image.clone();
-    dft(
-        &image_complex,
-        &mut image_dft,
-        DFT_COMPLEX_OUTPUT | DFT_COMPLEX_INPUT | DFT_SCALE,
-        0,
-    )?;
-
-    let mut vec_of_mat = VectorOfMat::new();
-    split(&image_dft, &mut vec_of_mat)?;
-
-    Ok((
-        fft_shift(&vec_of_mat.get(0)?)?,
-        fft_shift(&vec_of_mat.get(1)?)?,
-    ))
+// Degrades the image with every noise model and scores the low-pass filters
+// against the clean original.
+fn benchmark_low_pass(image: &Mat, seed: u64) -> Result<()> {
+    let models = [
+        Noise::Gaussian { sigma: 0.08 },
+        Noise::Poisson { photons: 30.0 },
+        Noise::SaltAndPepper {
+            amount: 0.05,
+            salt_ratio: 0.5,
+        },
+        Noise::Speckle { sigma: 0.2 },
+        Noise::Periodic {
+            amplitude: 0.1,
+            fx: 0.05,
+            fy: 0.02,
+            phase: 0.0,
+        },
+        Noise::MotionBlur {
+            length: 15.0,
+            angle: 30.0,
+        },
+        Noise::Defocus { radius: 4.0 },
+        Noise::GaussianBlur { sigma: 2.0 },
+    ];
+
+    for model in &models {
+        let degraded = degrade(image, *model, seed, 1.0)?;
+        println!(
+            "{:?}: noise rms {:.3}, psnr {:.2}, ssim {:.4}",
+            degraded.model,
+            degraded.noise_rms()?,
+            psnr(image, &degraded.image, 1.0)?,
+            ssim(image, &degraded.image, 1.0)?
+        );
+
+        let fft = fft_complex(&degraded.image)?;
+        for (name, filter) in &[
+            (
+                "perfect",
+                Box::new(Ideal { radius: 30.0 }) as Box<dyn FrequencyFilter>,
+            ),
+            ("butterworth", Box::new(Butterworth { radius: 30.0, n: 1 })),
+            ("gaussian", Box::new(Gaussian { radius: 30.0 })),
+        ] {
+            let filtered = ifft_complex(&apply_filter(&fft, filter)?, image.size()?)?;
+            println!(
+                "    {:<12} psnr {:.2}, ssim {:.4}",
+                name,
+                psnr(image, &filtered, 1.0)?,
+                ssim(image, &filtered, 1.0)?
+            );
+        }
+    }
+    Ok(())
 }
 
-fn fft_magnitude(fft: &(Mat, Mat)) -> Result<Mat> {
+fn fft_magnitude(fft: &(Mat, Mat)) -> Result<(Mat, Mapping)> {
     let mut image_magnitude = new_mat();
     magnitude(&fft.0, &fft.1, &mut image_magnitude)?;
-    let image = correction(&image_magnitude)?;
-    let image = mul_image(&image, 255.0)?;
-    Ok(image)
+    normalize(&image_magnitude, display_normalization()?, 255.0)
 }
 
-fn fft_magnitude_log(fft: &(Mat, Mat)) -> Result<Mat> {
-    let image_magnitude = fft_magnitude(&fft)?;
-    let image = mul_add_image(&image_magnitude, 1.0, 1.0 / 255.0)?;
-    let image = &log_image(&image)?;
-    let image = correction(&image)?;
-    Ok(image)
+fn fft_magnitude_log(fft: &(Mat, Mat)) -> Result<(Mat, Mapping)> {
+    normalize(&log_spectrum(fft)?, display_normalization()?, 1.0)
 }
 
-fn fft_shift(image: &Mat) -> Result<Mat> {
-    let clone = image.clone();
-    let cx = image.cols() / 2;
-    let cy = image.rows() / 2;
-    let mut q0 = Mat::roi(&clone, Rect::new(0, 0, cx, cy))?;
-    let mut q1 = Mat::roi(&clone, Rect::new(cx, 0, cx, cy))?;
-    let mut q2 = Mat::roi(&clone, Rect::new(0, cy, cx, cy))?;
-    let mut q3 = Mat::roi(&clone, Rect::new(cx, cy, cx, cy))?;
-
-    let mut tmp = q0.clone();
-    q0.copy_to(&mut tmp)?;
-    q3.copy_to(&mut q0)?;
-    tmp.copy_to(&mut q3)?;
-
-    q1.copy_to(&mut tmp)?;
-    q2.copy_to(&mut q1)?;
-    tmp.copy_to(&mut q2)?;
+// For the shared modules, which cannot assume whether the getter is fallible.
+fn channels(image: &Mat) -> Result<i32> {
+    image.channels()
+}
 
-    Ok(clone)
+fn depth(image: &Mat) -> Result<i32> {
+    image.depth()
 }
 
 fn new_mat() -> Mat {
@@ -182,36 +642,5 @@ fn log_image(image: &Mat) -> Result<Mat> {
 }
 
 fn correction(image: &Mat) -> Result<Mat> {
-    let mut min = 0.0;
-    let mut max = 0.0;
-    min_max_loc(
-        image,
-        Some(&mut min),
-        Some(&mut max),
-        None,
-        None,
-        &no_array()?,
-    )?;
-    println!("{}, {}", min, max);
-    let mut clone = image.clone();
-    image.convert_to(&mut clone, CV_32F, 1.0, -min)?;
-
-    let mut clone2 = clone.clone();
-    clone.convert_to(&mut clone2, CV_32F, 1.0 / (max - min), 0.0)?;
-    Ok(clone2)
-}
-
-fn flooded_image(image: &Mat, seed: (i32, i32), color: (u8, u8, u8)) -> Result<Mat> {
-    let mut clone = image.clone();
-    let (w, h) = (image.cols(), image.rows());
-    flood_fill(
-        &mut clone,
-        Point::new(seed.0, seed.1),
-        Scalar::new(color.0 as f64, color.1 as f64, color.2 as f64, 255.0),
-        &mut Rect::new(0, 0, w, h),
-        Scalar::new(0.0, 0.0, 0.0, 0.0),
-        Scalar::new(255.0, 255.0, 255.0, 255.0),
-        4,
-    )?;
-    Ok(clone)
+    Ok(normalize(image, display_normalization()?, 1.0)?.0)
 }
@@ -0,0 +1,227 @@
+use opencv::{
+    core::{self, integral2, mean, no_array, Mat, Rect, Size, CV_32F, CV_64F},
+    imgproc::{self, TM_CCOEFF_NORMED, TM_CCORR, TM_SQDIFF},
+    prelude::*,
+    Error, Result,
+};
+
+use crate::{
+    kernel::{Border, Kernel, Method},
+    new_mat,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MatchScore {
+    // Sum of squared differences, lower is better.
+    Ssd,
+    CrossCorrelation,
+    // Zero-mean normalised cross-correlation in [-1, 1].
+    Zncc,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Execution {
+    Spatial,
+    Fourier,
+    // Fourier once the template has more than `min_area` pixels.
+    Auto { min_area: i32 },
+}
+
+// `x`, `y` is the top-left corner of the template in the image.
+#[derive(Debug, Clone, Copy)]
+pub struct Match {
+    pub x: i32,
+    pub y: i32,
+    pub score: f32,
+}
+
+// Both images are single-channel CV_32F. The score map has one value per
+// position where the template fits entirely inside the image.
+pub fn match_template(
+    image: &Mat,
+    template: &Mat,
+    score: MatchScore,
+    execution: Execution,
+) -> Result<Mat> {
+    if template.rows() > image.rows() || template.cols() > image.cols() {
+        return Err(Error::new(
+            core::StsBadArg,
+            format!(
+                "template {:?} does not fit into image {:?}",
+                template.size()?,
+                image.size()?
+            ),
+        ));
+    }
+    let fourier = match execution {
+        Execution::Spatial => false,
+        Execution::Fourier => true,
+        Execution::Auto { min_area } => template.rows() * template.cols() > min_area,
+    };
+    if fourier {
+        return fourier_match(image, template, score);
+    }
+
+    let method = match score {
+        MatchScore::Ssd => TM_SQDIFF,
+        MatchScore::CrossCorrelation => TM_CCORR,
+        MatchScore::Zncc => TM_CCOEFF_NORMED,
+    };
+    let mut result = new_mat();
+    imgproc::match_template(image, template, &mut result, method, &no_array()?)?;
+    Ok(result)
+}
+
+// Best scores first; a match is dropped when its template rectangle would
+// overlap one that has already been accepted.
+pub fn best_matches(
+    scores: &Mat,
+    score: MatchScore,
+    template: Size,
+    count: usize,
+) -> Result<Vec<Match>> {
+    let mut candidates = vec![];
+    for i in 0..scores.rows() {
+        for j in 0..scores.cols() {
+            let value = *scores.at_2d::<f32>(i, j)?;
+            if value.is_finite() {
+                candidates.push(Match {
+                    x: j,
+                    y: i,
+                    score: value,
+                });
+            }
+        }
+    }
+    candidates.sort_by(|a, b| a.score.partial_cmp(&b.score).unwrap());
+    if score != MatchScore::Ssd {
+        candidates.reverse();
+    }
+
+    let mut matches: Vec<Match> = vec![];
+    for candidate in candidates {
+        if matches.len() >= count {
+            break;
+        }
+        let overlaps = matches.iter().any(|other| {
+            (candidate.x - other.x).abs() < template.width
+                && (candidate.y - other.y).abs() < template.height
+        });
+        if !overlaps {
+            matches.push(candidate);
+        }
+    }
+    Ok(matches)
+}
+
+// Cross-correlation through the DFT path of `Kernel`; SSD and ZNCC are
+// assembled from it and the windowed sums of the image, taken from its
+// integral images.
+fn fourier_match(image: &Mat, template: &Mat, score: MatchScore) -> Result<Mat> {
+    // The correlation is anchored in the template centre, the score map at
+    // its top-left corner.
+    let (rows, cols) = (
+        image.rows() - template.rows() + 1,
+        image.cols() - template.cols() + 1,
+    );
+    let correlation =
+        Kernel::from_mat(template)?.correlate_by(image, Border::Constant, Method::Fourier)?;
+    let correlation = Mat::roi(
+        &correlation,
+        Rect::new(template.cols() / 2, template.rows() / 2, cols, rows),
+    )?
+    .clone();
+    if score == MatchScore::CrossCorrelation {
+        return Ok(correlation);
+    }
+
+    let mut sum = new_mat();
+    let mut sum_sq = new_mat();
+    integral2(image, &mut sum, &mut sum_sq, CV_64F, CV_64F)?;
+    let window = |integral: &Mat, i: i32, j: i32| -> Result<f64> {
+        let (h, w) = (template.rows(), template.cols());
+        Ok(*integral.at_2d::<f64>(i + h, j + w)?
+            - *integral.at_2d::<f64>(i, j + w)?
+            - *integral.at_2d::<f64>(i + h, j)?
+            + *integral.at_2d::<f64>(i, j)?)
+    };
+
+    let n = (template.rows() * template.cols()) as f64;
+    let template_mean = mean(template, &no_array()?)?[0];
+    let template_sq = template.dot(template)?;
+    let template_var = template_sq - n * template_mean * template_mean;
+
+    let mut result = Mat::zeros(rows, cols, CV_32F)?.to_mat()?;
+    for i in 0..rows {
+        for j in 0..cols {
+            let cc = *correlation.at_2d::<f32>(i, j)? as f64;
+            let (s, s_sq) = (window(&sum, i, j)?, window(&sum_sq, i, j)?);
+            let value = match score {
+                MatchScore::Ssd => (s_sq - 2.0 * cc + template_sq).max(0.0),
+                _ => {
+                    let image_var = (s_sq - s * s / n).max(0.0);
+                    let denominator = (image_var * template_var).sqrt();
+                    if denominator > f64::EPSILON {
+                        (cc - s * template_mean) / denominator
+                    } else {
+                        0.0
+                    }
+                }
+            };
+            *result.at_2d_mut::<f32>(i, j)? = value as f32;
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fourier_scores_match_the_spatial_ones() -> Result<()> {
+        let mut image = Mat::zeros(40, 50, CV_32F)?.to_mat()?;
+        for i in 0..40 {
+            for j in 0..50 {
+                let value = ((i * 31 + j * 17 + i * j) % 23) as f64 / 23.0 + (j as f64 / 7.0).sin();
+                *image.at_2d_mut::<f32>(i, j)? = value as f32;
+            }
+        }
+        for &(rows, cols) in &[(7, 9), (8, 6)] {
+            let template = Mat::roi(&image, Rect::new(20, 11, cols, rows))?.clone();
+            for &score in &[
+                MatchScore::Ssd,
+                MatchScore::CrossCorrelation,
+                MatchScore::Zncc,
+            ] {
+                let spatial = match_template(&image, &template, score, Execution::Spatial)?;
+                let fourier = match_template(&image, &template, score, Execution::Fourier)?;
+                assert_eq!(spatial.size()?, fourier.size()?);
+                // SSD and cross-correlation are sums over the template, so
+                // their rounding grows with its energy.
+                let tolerance = match score {
+                    MatchScore::Zncc => 1e-3,
+                    _ => 1e-4 * template.dot(&template)?,
+                };
+                for (s, f) in spatial
+                    .data_typed::<f32>()?
+                    .iter()
+                    .zip(fourier.data_typed::<f32>()?)
+                {
+                    assert!(
+                        ((s - f).abs() as f64) < tolerance,
+                        "{:?}: {} {}",
+                        score,
+                        s,
+                        f
+                    );
+                }
+                if score != MatchScore::CrossCorrelation {
+                    let best = best_matches(&fourier, score, template.size()?, 1)?;
+                    assert_eq!((best[0].x, best[0].y), (20, 11));
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,165 @@
+use opencv::{
+    core::{Mat, Point, Size, BORDER_DEFAULT, CV_8UC1},
+    imgproc::{blur, dilate, morphology_default_border_value},
+    prelude::*,
+    Result,
+};
+
+use crate::{filters::FrequencyFilter, new_mat, shapes::Profile, spectrum::log_spectrum};
+
+// Notches are (du, dv) offsets from the zero frequency of the shifted
+// spectrum, in pixels along the columns and rows. Every notch is mirrored
+// to (-du, -dv), since the spectrum of a real image is symmetric;
+// `complement` gives the notch pass.
+#[derive(Debug, Clone)]
+pub struct NotchReject {
+    pub notches: Vec<(i32, i32)>,
+    pub radius: f64,
+    pub shape: Profile,
+}
+
+impl FrequencyFilter for NotchReject {
+    fn response(&self, u: f64, v: f64) -> f64 {
+        self.notches
+            .iter()
+            .map(|&(du, dv)| {
+                let (du, dv) = (du as f64, dv as f64);
+                let low_pass = |u: f64, v: f64| self.shape.low_pass(u.hypot(v) / self.radius);
+                (1.0 - low_pass(u - du, v - dv)) * (1.0 - low_pass(u + du, v + dv))
+            })
+            .product()
+    }
+}
+
+// How `detect_peaks` looks for isolated spikes in the log magnitude: local
+// maxima that stand more than `k` standard deviations above the locally
+// averaged spectrum, at least `exclude_radius` away from the zero frequency.
+// At most `max_peaks` are kept, strongest first.
+#[derive(Debug, Clone, Copy)]
+pub struct PeakSearch {
+    pub exclude_radius: f64,
+    pub k: f64,
+    pub max_peaks: usize,
+}
+
+// Only one peak of every symmetric pair is reported.
+pub fn detect_peaks(fft: &(Mat, Mat), search: &PeakSearch) -> Result<Vec<(i32, i32)>> {
+    let spectrum = log_spectrum(fft)?;
+    let background = {
+        let mut clone = new_mat();
+        blur(
+            &spectrum,
+            &mut clone,
+            Size::new(15, 15),
+            Point::new(-1, -1),
+            BORDER_DEFAULT,
+        )?;
+        clone
+    };
+    let local_max = {
+        let mut clone = new_mat();
+        dilate(
+            &spectrum,
+            &mut clone,
+            &Mat::ones(3, 3, CV_8UC1)?.to_mat()?,
+            Point::new(-1, -1),
+            1,
+            BORDER_DEFAULT,
+            morphology_default_border_value()?,
+        )?;
+        clone
+    };
+
+    let (center_row, center_col) = (spectrum.rows() / 2, spectrum.cols() / 2);
+    let mut candidates = vec![];
+    for i in 0..spectrum.rows() {
+        for j in 0..spectrum.cols() {
+            let (du, dv) = (j - center_col, i - center_row);
+            if ((du * du + dv * dv) as f64).sqrt() <= search.exclude_radius {
+                continue;
+            }
+            let value = *spectrum.at_2d::<f32>(i, j)?;
+            let excess = value - *background.at_2d::<f32>(i, j)?;
+            candidates.push((du, dv, excess, value >= *local_max.at_2d::<f32>(i, j)?));
+        }
+    }
+
+    let count = candidates.len().max(1) as f64;
+    let mean = candidates.iter().map(|c| c.2 as f64).sum::<f64>() / count;
+    let std = (candidates
+        .iter()
+        .map(|c| (c.2 as f64 - mean).powi(2))
+        .sum::<f64>()
+        / count)
+        .sqrt();
+
+    let mut peaks: Vec<(i32, i32, f32)> = candidates
+        .into_iter()
+        .filter(|&(du, dv, excess, is_max)| {
+            is_max && (excess as f64) > mean + search.k * std && (dv > 0 || (dv == 0 && du > 0))
+        })
+        .map(|(du, dv, excess, _)| (du, dv, excess))
+        .collect();
+    peaks.sort_by(|a, b| b.2.partial_cmp(&a.2).unwrap());
+    Ok(peaks
+        .into_iter()
+        .take(search.max_peaks)
+        .map(|(du, dv, _)| (du, dv))
+        .collect())
+}
+
+pub fn auto_notch_reject(
+    fft: &(Mat, Mat),
+    radius: f64,
+    shape: Profile,
+    search: &PeakSearch,
+) -> Result<NotchReject> {
+    Ok(NotchReject {
+        notches: detect_peaks(fft, search)?,
+        radius,
+        shape,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use opencv::core::CV_32F;
+
+    use super::*;
+    use crate::{
+        fft::{fft_complex, ifft_complex},
+        filters::apply_filter,
+    };
+
+    // A flat image with a cosine of 16 cycles along the columns and 8 along
+    // the rows, whose spectrum is zero apart from the zero frequency and the
+    // pair at ±(16, 8).
+    #[test]
+    fn a_sinusoid_is_found_and_suppressed() -> Result<()> {
+        let mut image = Mat::zeros(64, 64, CV_32F)?.to_mat()?;
+        for i in 0..64 {
+            for j in 0..64 {
+                let phase = 2.0 * std::f64::consts::PI * (16 * j + 8 * i) as f64 / 64.0;
+                *image.at_2d_mut::<f32>(i, j)? = (0.5 + 0.25 * phase.cos()) as f32;
+            }
+        }
+        let fft = fft_complex(&image)?;
+        let search = PeakSearch {
+            exclude_radius: 12.0,
+            k: 4.0,
+            max_peaks: 8,
+        };
+        assert_eq!(detect_peaks(&fft, &search)?, vec![(16, 8)]);
+
+        // The Butterworth notches still take a quarter of a percent off the
+        // zero frequency; the cosine itself has an amplitude of 0.25.
+        for name in Profile::NAMES {
+            let notch = auto_notch_reject(&fft, 4.0, Profile::by_name(name).unwrap(), &search)?;
+            let image_notched = ifft_complex(&apply_filter(&fft, &notch)?, image.size()?)?;
+            for &value in image_notched.data_typed::<f32>()? {
+                assert!((value - 0.5).abs() < 0.01, "{}: {}", name, value);
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,296 @@
+use opencv::{
+    core::{self, Mat, Point2f, Scalar, Size, BORDER_CONSTANT, CV_32F},
+    imgproc::{get_rotation_matrix_2d, warp_affine, warp_polar, INTER_LINEAR, WARP_POLAR_LOG},
+    prelude::*,
+    Error, Result,
+};
+
+use std::f64::consts::PI;
+
+use crate::{
+    fft_complex, fft_shift, ifft_complex, mul_image, mul_mat_image, new_mat, spectrum::log_spectrum,
+};
+
+// `dx`, `dy` move the first image onto the second; `response` is the height of
+// the correlation peak, 1 for a pure cyclic shift and close to 0 for
+// unrelated images.
+#[derive(Debug, Clone, Copy)]
+pub struct Translation {
+    pub dx: f64,
+    pub dy: f64,
+    pub response: f64,
+}
+
+// The second image is the first one rotated counter-clockwise by `angle`
+// degrees and scaled by `scale` about its centre, then translated.
+#[derive(Debug, Clone, Copy)]
+pub struct Similarity {
+    pub angle: f64,
+    pub scale: f64,
+    pub translation: Translation,
+}
+
+pub fn phase_correlation(first: &Mat, second: &Mat) -> Result<Translation> {
+    if first.size()? != second.size()? {
+        return Err(Error::new(
+            core::StsBadArg,
+            format!(
+                "phase correlation needs equal sizes, got {:?} and {:?}",
+                first.size()?,
+                second.size()?
+            ),
+        ));
+    }
+    let fft_first = fft_complex(first)?;
+    let fft_second = fft_complex(second)?;
+
+    // F2 · F1* / |F2 · F1*| keeps only the phase difference; its inverse
+    // transform peaks at the shift from the first image to the second.
+    let (mut re, mut im) = (fft_second.0.clone(), fft_second.1.clone());
+    {
+        let (first_re, first_im) = (
+            fft_first.0.data_typed::<f32>()?,
+            fft_first.1.data_typed::<f32>()?,
+        );
+        let re_values = re.data_typed_mut::<f32>()?;
+        let im_values = im.data_typed_mut::<f32>()?;
+        for i in 0..re_values.len() {
+            let (a_re, a_im) = (first_re[i] as f64, first_im[i] as f64);
+            let (b_re, b_im) = (re_values[i] as f64, im_values[i] as f64);
+            let (cross_re, cross_im) = (b_re * a_re + b_im * a_im, b_im * a_re - b_re * a_im);
+            let norm = (cross_re * cross_re + cross_im * cross_im).sqrt();
+            let (value_re, value_im) = if norm > f64::EPSILON {
+                (cross_re / norm, cross_im / norm)
+            } else {
+                (0.0, 0.0)
+            };
+            re_values[i] = value_re as f32;
+            im_values[i] = value_im as f32;
+        }
+    }
+
+    let size = fft_first.0.size()?;
+    let surface = ifft_complex(&(re, im), size)?;
+    let surface = fft_shift(&mul_image(
+        &surface,
+        1.0 / (size.width * size.height) as f64,
+    )?)?;
+    peak(&surface)
+}
+
+// Rotation and scale turn into shifts of the log-polar magnitude spectrum,
+// which does not depend on translation. The magnitude spectrum is symmetric,
+// so the angle is only known modulo 180 degrees and is reported in (-90, 90].
+pub fn log_polar_registration(first: &Mat, second: &Mat) -> Result<Similarity> {
+    let polar_first = log_polar_spectrum(first)?;
+    let polar_second = log_polar_spectrum(second)?;
+    let shift = phase_correlation(&polar_first, &polar_second)?;
+
+    // Rows of the polar image cover 360 degrees clockwise, columns cover
+    // ln(radius) up to ln(max_radius); a larger image has a smaller spectrum.
+    let (cols, rows) = (polar_first.cols(), polar_first.rows());
+    let max_radius = (cols.min(rows) / 2) as f64;
+    let rotation = -shift.dy * 360.0 / rows as f64;
+    let angle = 90.0 - (90.0 - rotation).rem_euclid(180.0);
+    let scale = (-shift.dx * max_radius.ln() / cols as f64).exp();
+
+    let center = Point2f::new(second.cols() as f32 / 2.0, second.rows() as f32 / 2.0);
+    let undo = get_rotation_matrix_2d(center, -angle, 1.0 / scale)?;
+    let mut second_aligned = new_mat();
+    warp_affine(
+        second,
+        &mut second_aligned,
+        &undo,
+        second.size()?,
+        INTER_LINEAR,
+        BORDER_CONSTANT,
+        Scalar::all(0.0),
+    )?;
+
+    // The shift left after undoing rotation and scale is measured in the
+    // frame of the first image; rotate and scale it back into the second.
+    let residual = phase_correlation(first, &second_aligned)?;
+    let (alpha, beta) = (
+        scale * angle.to_radians().cos(),
+        scale * angle.to_radians().sin(),
+    );
+    Ok(Similarity {
+        angle,
+        scale,
+        translation: Translation {
+            dx: alpha * residual.dx + beta * residual.dy,
+            dy: -beta * residual.dx + alpha * residual.dy,
+            response: residual.response,
+        },
+    })
+}
+
+fn log_polar_spectrum(image: &Mat) -> Result<Mat> {
+    let spectrum = log_spectrum(&fft_complex(image)?)?;
+    let spectrum = mul_mat_image(&spectrum, &high_pass(spectrum.size()?)?)?;
+
+    let size = Size::new(spectrum.cols(), spectrum.rows());
+    let mut polar = new_mat();
+    warp_polar(
+        &spectrum,
+        &mut polar,
+        size,
+        Point2f::new((size.width / 2) as f32, (size.height / 2) as f32),
+        (size.width.min(size.height) / 2) as f64,
+        INTER_LINEAR + WARP_POLAR_LOG,
+    )?;
+    Ok(polar)
+}
+
+// (1 - X)(2 - X) with X = cos(pi u) cos(pi v), after Reddy and Chatterji: it
+// suppresses the low frequencies every spectrum shares, which would otherwise
+// pull the log-polar correlation towards no rotation and no scaling.
+fn high_pass(size: Size) -> Result<Mat> {
+    let mut emphasis =
+        Mat::new_rows_cols_with_default(size.height, size.width, CV_32F, Scalar::all(0.0))?;
+    for i in 0..size.height {
+        let v = (i - size.height / 2) as f64 / size.height as f64;
+        for j in 0..size.width {
+            let u = (j - size.width / 2) as f64 / size.width as f64;
+            let x = (PI * u).cos() * (PI * v).cos();
+            *emphasis.at_2d_mut::<f32>(i, j)? = ((1.0 - x) * (2.0 - x)) as f32;
+        }
+    }
+    Ok(emphasis)
+}
+
+// Highest value of a centred correlation surface, refined to sub-pixel
+// accuracy by fitting a parabola through it and its neighbours on each axis.
+fn peak(surface: &Mat) -> Result<Translation> {
+    let (rows, cols) = (surface.rows(), surface.cols());
+    let (mut best_row, mut best_col, mut best) = (0, 0, f32::MIN);
+    for i in 0..rows {
+        for j in 0..cols {
+            let value = *surface.at_2d::<f32>(i, j)?;
+            if value > best {
+                best = value;
+                best_row = i;
+                best_col = j;
+            }
+        }
+    }
+
+    let at = |i: i32, j: i32| -> Result<f64> {
+        Ok(*surface.at_2d::<f32>(i.rem_euclid(rows), j.rem_euclid(cols))? as f64)
+    };
+    let offset = |left: f64, center: f64, right: f64| {
+        let denominator = left - 2.0 * center + right;
+        if denominator.abs() > f64::EPSILON {
+            (0.5 * (left - right) / denominator).clamp(-0.5, 0.5)
+        } else {
+            0.0
+        }
+    };
+    let center = best as f64;
+    let offset_x = offset(
+        at(best_row, best_col - 1)?,
+        center,
+        at(best_row, best_col + 1)?,
+    );
+    let offset_y = offset(
+        at(best_row - 1, best_col)?,
+        center,
+        at(best_row + 1, best_col)?,
+    );
+
+    Ok(Translation {
+        dx: (best_col - cols / 2) as f64 + offset_x,
+        dy: (best_row - rows / 2) as f64 + offset_y,
+        response: center,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::roll;
+    use opencv::{core::BORDER_REPLICATE, imgproc::gaussian_blur};
+
+    // Smoothed noise faded out towards a disc, so warping it never moves
+    // content across the border.
+    fn texture(size: i32) -> Result<Mat> {
+        let mut noise = Mat::new_rows_cols_with_default(size, size, CV_32F, Scalar::all(0.0))?;
+        let mut state = 1u32;
+        for value in noise.data_typed_mut::<f32>()? {
+            state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            *value = (state >> 8) as f32 / (1u32 << 24) as f32;
+        }
+        let mut smooth = new_mat();
+        gaussian_blur(
+            &noise,
+            &mut smooth,
+            Size::new(7, 7),
+            1.0,
+            1.0,
+            BORDER_REPLICATE,
+        )?;
+
+        let center = (size / 2) as f64;
+        for i in 0..size {
+            for j in 0..size {
+                let r = (i as f64 - center).hypot(j as f64 - center) / (0.3 * size as f64);
+                let fade = if r < 1.0 {
+                    0.5 + 0.5 * (PI * r).cos()
+                } else {
+                    0.0
+                };
+                *smooth.at_2d_mut::<f32>(i, j)? *= fade as f32;
+            }
+        }
+        Ok(smooth)
+    }
+
+    #[test]
+    fn phase_correlation_finds_a_cyclic_shift() -> Result<()> {
+        let first = texture(128)?;
+        let second = roll(&first, 12, -7)?;
+        let shift = phase_correlation(&first, &second)?;
+        assert!((shift.dx - 12.0).abs() < 0.1, "dx = {}", shift.dx);
+        assert!((shift.dy + 7.0).abs() < 0.1, "dy = {}", shift.dy);
+        assert!(shift.response > 0.9);
+        Ok(())
+    }
+
+    #[test]
+    fn log_polar_registration_recovers_a_similarity() -> Result<()> {
+        let first = texture(256)?;
+        let (angle, scale, dx, dy) = (10.0, 1.1, 10.0, -6.0);
+        let mut transform = get_rotation_matrix_2d(Point2f::new(128.0, 128.0), angle, scale)?;
+        *transform.at_2d_mut::<f64>(0, 2)? += dx;
+        *transform.at_2d_mut::<f64>(1, 2)? += dy;
+        let mut second = new_mat();
+        warp_affine(
+            &first,
+            &mut second,
+            &transform,
+            first.size()?,
+            INTER_LINEAR,
+            BORDER_CONSTANT,
+            Scalar::all(0.0),
+        )?;
+
+        let found = log_polar_registration(&first, &second)?;
+        assert!((found.angle - angle).abs() < 1.5, "angle = {}", found.angle);
+        assert!(
+            (found.scale / scale - 1.0).abs() < 0.03,
+            "scale = {}",
+            found.scale
+        );
+        assert!(
+            (found.translation.dx - dx).abs() < 1.5,
+            "dx = {}",
+            found.translation.dx
+        );
+        assert!(
+            (found.translation.dy - dy).abs() < 1.5,
+            "dy = {}",
+            found.translation.dy
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,367 @@
+use std::f64::consts::PI;
+
+use crate::filters::FrequencyFilter;
+
+// Cuts off sharply at `radius`, so the filtered image rings.
+#[derive(Debug, Clone, Copy)]
+pub struct Ideal {
+    pub radius: f64,
+}
+
+impl FrequencyFilter for Ideal {
+    fn response(&self, u: f64, v: f64) -> f64 {
+        if u.hypot(v) <= self.radius {
+            1.0
+        } else {
+            0.0
+        }
+    }
+}
+
+// Band filters keep a ring of frequencies `width` wide around the distance
+// `center` from the zero frequency; `complement` gives the band reject.
+#[derive(Debug, Clone, Copy)]
+pub struct IdealBand {
+    pub center: f64,
+    pub width: f64,
+}
+
+impl FrequencyFilter for IdealBand {
+    fn response(&self, u: f64, v: f64) -> f64 {
+        if (u.hypot(v) - self.center).abs() <= self.width / 2.0 {
+            1.0
+        } else {
+            0.0
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ButterworthBand {
+    pub center: f64,
+    pub width: f64,
+    pub n: i32,
+}
+
+impl FrequencyFilter for ButterworthBand {
+    fn response(&self, u: f64, v: f64) -> f64 {
+        let dist = u.hypot(v).max(1e-6);
+        let ratio = dist * self.width / (dist.powi(2) - self.center.powi(2));
+        1.0 - 1.0 / (1.0 + ratio.powi(2 * self.n))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct GaussianBand {
+    pub center: f64,
+    pub width: f64,
+}
+
+impl FrequencyFilter for GaussianBand {
+    fn response(&self, u: f64, v: f64) -> f64 {
+        let dist = u.hypot(v).max(1e-6);
+        let ratio = (dist.powi(2) - self.center.powi(2)) / (dist * self.width);
+        (-ratio.powi(2)).exp()
+    }
+}
+
+// Low-pass fall-off as a function of the distance in units of the cutoff.
+#[derive(Debug, Clone, Copy)]
+pub enum Profile {
+    Ideal,
+    Butterworth { n: i32 },
+    Gaussian,
+}
+
+impl Profile {
+    pub const NAMES: &'static [&'static str] = &["ideal", "butterworth", "gaussian"];
+
+    pub fn by_name(name: &str) -> Option<Profile> {
+        match name {
+            "ideal" => Some(Profile::Ideal),
+            "butterworth" => Some(Profile::Butterworth { n: 2 }),
+            "gaussian" => Some(Profile::Gaussian),
+            _ => None,
+        }
+    }
+
+    pub fn low_pass(self, dist: f64) -> f64 {
+        match self {
+            Profile::Ideal => {
+                if dist <= 1.0 {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            Profile::Butterworth { n } => 1.0 / (1.0 + dist.powi(2 * n)),
+            Profile::Gaussian => (-dist * dist / 2.0).exp(),
+        }
+    }
+}
+
+// Low-pass with cutoff `radius_u` along the direction `angle` (degrees, from
+// the u axis towards v) and `radius_v` across it, e.g. to suppress scan-line
+// artefacts that only spread along one axis.
+#[derive(Debug, Clone, Copy)]
+pub struct Elliptic {
+    pub radius_u: f64,
+    pub radius_v: f64,
+    pub angle: f64,
+    pub profile: Profile,
+}
+
+impl FrequencyFilter for Elliptic {
+    fn response(&self, u: f64, v: f64) -> f64 {
+        let (u, v) = rotate(u, v, -self.angle);
+        self.profile
+            .low_pass((u / self.radius_u).hypot(v / self.radius_v))
+    }
+}
+
+// Keeps the frequencies whose direction lies within `width` degrees around
+// `angle`, together with the opposite directions so the result stays real.
+// The edges fall off as a raised cosine over `softness` degrees; the zero
+// frequency is always kept.
+#[derive(Debug, Clone, Copy)]
+pub struct Wedge {
+    pub angle: f64,
+    pub width: f64,
+    pub softness: f64,
+}
+
+impl FrequencyFilter for Wedge {
+    fn response(&self, u: f64, v: f64) -> f64 {
+        if u == 0.0 && v == 0.0 {
+            return 1.0;
+        }
+        let direction = v.atan2(u).to_degrees();
+        // Distance between orientations, which repeat every 180 degrees.
+        let offset = (direction - self.angle).rem_euclid(180.0);
+        let offset = offset.min(180.0 - offset);
+        let edge = self.width / 2.0;
+        if offset <= edge {
+            1.0
+        } else if offset < edge + self.softness {
+            0.5 + 0.5 * (PI * (offset - edge) / self.softness).cos()
+        } else {
+            0.0
+        }
+    }
+}
+
+// A pair of Gaussians at distance `frequency` from the zero frequency in the
+// direction `angle` and its opposite: the spectrum of a real Gabor kernel.
+// `sigma_radial` and `sigma_angular` are the widths along and across that
+// direction.
+#[derive(Debug, Clone, Copy)]
+pub struct Gabor {
+    pub frequency: f64,
+    pub angle: f64,
+    pub sigma_radial: f64,
+    pub sigma_angular: f64,
+}
+
+impl FrequencyFilter for Gabor {
+    fn response(&self, u: f64, v: f64) -> f64 {
+        let (u, v) = rotate(u, v, -self.angle);
+        let lobe = |u: f64| {
+            (-(u / self.sigma_radial).powi(2) / 2.0 - (v / self.sigma_angular).powi(2) / 2.0).exp()
+        };
+        (lobe(u - self.frequency) + lobe(u + self.frequency)).min(1.0)
+    }
+}
+
+// (u, v) rotated by `angle` degrees, from the u axis towards v.
+pub fn rotate(u: f64, v: f64, angle: f64) -> (f64, f64) {
+    let (sin, cos) = angle.to_radians().sin_cos();
+    (u * cos - v * sin, u * sin + v * cos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    // (u, v) at distance `radius` in the direction `angle` (degrees).
+    fn polar(radius: f64, angle: f64) -> (f64, f64) {
+        let (sin, cos) = angle.to_radians().sin_cos();
+        (radius * cos, radius * sin)
+    }
+
+    #[test]
+    fn profiles_fall_from_one_through_their_cutoff() {
+        let profiles = [
+            Profile::Ideal,
+            Profile::Butterworth { n: 2 },
+            Profile::Gaussian,
+        ];
+        for &profile in &profiles {
+            assert!(close(profile.low_pass(0.0), 1.0));
+            for i in 0..30 {
+                let dist = i as f64 / 10.0;
+                assert!(profile.low_pass(dist + 0.1) <= profile.low_pass(dist));
+            }
+        }
+        assert!(close(Profile::Ideal.low_pass(1.0), 1.0));
+        assert!(close(Profile::Ideal.low_pass(1.001), 0.0));
+        assert!(close(Profile::Butterworth { n: 2 }.low_pass(1.0), 0.5));
+        assert!(close(
+            Profile::Butterworth { n: 2 }.low_pass(2.0),
+            1.0 / 17.0
+        ));
+        assert!(close(Profile::Gaussian.low_pass(1.0), (-0.5f64).exp()));
+    }
+
+    #[test]
+    fn elliptic_cutoffs_follow_the_axes_and_the_angle() {
+        let elliptic = Elliptic {
+            radius_u: 10.0,
+            radius_v: 5.0,
+            angle: 0.0,
+            profile: Profile::Butterworth { n: 1 },
+        };
+        assert!(close(elliptic.response(10.0, 0.0), 0.5));
+        assert!(close(elliptic.response(0.0, 5.0), 0.5));
+
+        let turned = Elliptic {
+            angle: 90.0,
+            ..elliptic
+        };
+        assert!(close(turned.response(0.0, 10.0), 0.5));
+        assert!(close(turned.response(5.0, 0.0), 0.5));
+
+        let oblique = Elliptic {
+            angle: 30.0,
+            ..elliptic
+        };
+        let (u, v) = polar(10.0, 30.0);
+        assert!(close(oblique.response(u, v), 0.5));
+    }
+
+    #[test]
+    fn wedge_keeps_a_band_of_orientations() {
+        let wedge = Wedge {
+            angle: 30.0,
+            width: 20.0,
+            softness: 10.0,
+        };
+        assert!(close(wedge.response(0.0, 0.0), 1.0));
+        for &(angle, expected) in &[
+            (30.0, 1.0),
+            (210.0, 1.0),
+            (38.0, 1.0),
+            (45.0, 0.5),
+            (195.0, 0.5),
+            (60.0, 0.0),
+            (120.0, 0.0),
+        ] {
+            let (u, v) = polar(25.0, angle);
+            assert!(close(wedge.response(u, v), expected), "{} degrees", angle);
+        }
+    }
+
+    #[test]
+    fn gabor_peaks_at_its_frequency_in_both_directions() {
+        let gabor = Gabor {
+            frequency: 20.0,
+            angle: 45.0,
+            sigma_radial: 4.0,
+            sigma_angular: 4.0,
+        };
+        for &angle in &[45.0, 225.0] {
+            let (u, v) = polar(20.0, angle);
+            assert!((gabor.response(u, v) - 1.0).abs() < 1e-3);
+        }
+        let (u, v) = polar(20.0, 135.0);
+        assert!(gabor.response(u, v) < 1e-3);
+        assert!(gabor.response(0.0, 0.0) < 1e-3);
+    }
+
+    // Every filter applied to the spectrum of a real image has to keep
+    // H(u, v) = H(-u, -v), or the filtered image would not be real.
+    #[test]
+    fn oriented_filters_are_point_symmetric() {
+        let filters: [Box<dyn FrequencyFilter>; 3] = [
+            Box::new(Elliptic {
+                radius_u: 12.0,
+                radius_v: 4.0,
+                angle: 25.0,
+                profile: Profile::Gaussian,
+            }),
+            Box::new(Wedge {
+                angle: 70.0,
+                width: 30.0,
+                softness: 15.0,
+            }),
+            Box::new(Gabor {
+                frequency: 15.0,
+                angle: 100.0,
+                sigma_radial: 3.0,
+                sigma_angular: 5.0,
+            }),
+        ];
+        for filter in &filters {
+            for i in 0..36 {
+                let (u, v) = polar(3.0 + i as f64, 10.0 * i as f64);
+                assert!(
+                    close(filter.response(u, v), filter.response(-u, -v)),
+                    "{:?}",
+                    filter
+                );
+            }
+        }
+    }
+
+    // The distances below and above `center` at which d·width = |d² - center²|,
+    // i.e. where the Butterworth band falls to 1/2 and the Gaussian to 1/e.
+    fn band_edges(center: f64, width: f64) -> (f64, f64) {
+        let root = (width * width + 4.0 * center * center).sqrt();
+        ((root - width) / 2.0, (root + width) / 2.0)
+    }
+
+    #[test]
+    fn bands_pass_their_center_and_fall_off_at_their_edges() {
+        let (center, width) = (40.0, 20.0);
+        let ideal = IdealBand { center, width };
+        for &dist in &[30.0, 40.0, 50.0] {
+            assert!(close(ideal.response(dist, 0.0), 1.0), "{}", dist);
+            assert!(
+                close(ideal.complement().response(0.0, dist), 0.0),
+                "{}",
+                dist
+            );
+        }
+        for &dist in &[0.0, 29.9, 50.1] {
+            assert!(close(ideal.response(dist, 0.0), 0.0), "{}", dist);
+        }
+
+        let butterworth = ButterworthBand {
+            center,
+            width,
+            n: 2,
+        };
+        let gaussian = GaussianBand { center, width };
+        for &(band, edge) in &[
+            (&butterworth as &dyn FrequencyFilter, 0.5),
+            (&gaussian, (-1.0f64).exp()),
+        ] {
+            assert!(close(band.response(0.0, center), 1.0), "{:?}", band);
+            assert!(
+                close(band.complement().response(center, 0.0), 0.0),
+                "{:?}",
+                band
+            );
+            let (inner, outer) = band_edges(center, width);
+            for &dist in &[inner, outer] {
+                let (u, v) = (dist * 0.6, dist * 0.8);
+                assert!(close(band.response(u, v), edge), "{:?} at {}", band, dist);
+            }
+            assert!(band.response(0.0, 0.0) < 1e-9, "{:?}", band);
+            assert!(band.response(4.0 * center, 0.0) < 0.01, "{:?}", band);
+        }
+    }
+}
@@ -0,0 +1,341 @@
+use opencv::{
+    core::{
+        copy_make_border, magnitude, merge, phase, Mat, Point, Scalar, BORDER_CONSTANT, CV_8U,
+        CV_8UC3,
+    },
+    imgproc::{
+        apply_color_map, cvt_color, line, put_text, COLORMAP_JET, COLORMAP_MAGMA, COLORMAP_VIRIDIS,
+        COLOR_HSV2BGR, FONT_HERSHEY_SIMPLEX, LINE_8,
+    },
+    prelude::*,
+    types::VectorOfMat,
+    Result,
+};
+
+use crate::{correction, log_image, mul_add_image, mul_image, new_mat};
+
+#[derive(Debug, Clone, Copy)]
+pub enum ColorMap {
+    Viridis,
+    Jet,
+    Magma,
+}
+
+impl ColorMap {
+    pub const NAMES: &'static [&'static str] = &["viridis", "jet", "magma"];
+
+    pub fn by_name(name: &str) -> Option<ColorMap> {
+        match name {
+            "viridis" => Some(ColorMap::Viridis),
+            "jet" => Some(ColorMap::Jet),
+            "magma" => Some(ColorMap::Magma),
+            _ => None,
+        }
+    }
+}
+
+const MARGIN: i32 = 40;
+
+// log(1 + |F|), the one scale every spectrum is displayed and searched on.
+// `fft_complex` divides by the number of pixels, which would leave all but
+// the zero frequency on the linear part of the logarithm, so that is undone.
+pub fn log_spectrum(fft: &(Mat, Mat)) -> Result<Mat> {
+    let mut image_magnitude = new_mat();
+    magnitude(&fft.0, &fft.1, &mut image_magnitude)?;
+    let pixels = (fft.0.rows() * fft.0.cols()) as f64;
+    log_image(&mul_add_image(&image_magnitude, pixels, 1.0)?)
+}
+
+// `log_spectrum` stretched to [0, 1] for display.
+pub fn log_magnitude(fft: &(Mat, Mat)) -> Result<Mat> {
+    correction(&log_spectrum(fft)?)
+}
+
+// Phase as hue and log magnitude as brightness, so that the phase of
+// frequencies with no energy does not cover the picture in noise.
+pub fn phase_wheel(fft: &(Mat, Mat)) -> Result<Mat> {
+    let mut angle = new_mat();
+    phase(&fft.0, &fft.1, &mut angle, true)?;
+    let hue = to_8u(&angle, 180.0 / 360.0)?;
+    let saturation =
+        Mat::new_rows_cols_with_default(angle.rows(), angle.cols(), CV_8U, Scalar::all(255.0))?;
+    let value = to_8u(&log_magnitude(fft)?, 255.0)?;
+
+    let mut hsv = new_mat();
+    merge(&VectorOfMat::from(vec![hue, saturation, value]), &mut hsv)?;
+    let mut bgr = new_mat();
+    cvt_color(&hsv, &mut bgr, COLOR_HSV2BGR, 0)?;
+    Ok(bgr)
+}
+
+// `image` in [0, 1]; the result is an 8-bit BGR image.
+pub fn color_mapped(image: &Mat, map: ColorMap) -> Result<Mat> {
+    let map = match map {
+        ColorMap::Viridis => COLORMAP_VIRIDIS,
+        ColorMap::Jet => COLORMAP_JET,
+        ColorMap::Magma => COLORMAP_MAGMA,
+    };
+    let mut bgr = new_mat();
+    apply_color_map(&to_8u(image, 255.0)?, &mut bgr, map)?;
+    Ok(bgr)
+}
+
+// Frames an 8-bit BGR picture of a shifted spectrum with ticks every `step`
+// cycles per pixel along u (bottom) and v (left).
+pub fn annotate_axes(spectrum: &Mat, step: f64) -> Result<Mat> {
+    let mut framed = new_mat();
+    copy_make_border(
+        spectrum,
+        &mut framed,
+        0,
+        MARGIN,
+        MARGIN,
+        0,
+        BORDER_CONSTANT,
+        Scalar::all(0.0),
+    )?;
+    let (cols, rows) = (spectrum.cols(), spectrum.rows());
+    let ticks = (0.5 / step).floor() as i32;
+    for k in -ticks..=ticks {
+        let frequency = k as f64 * step;
+        let x = MARGIN + cols / 2 + (frequency * cols as f64).round() as i32;
+        let y = rows / 2 + (frequency * rows as f64).round() as i32;
+        if (MARGIN..MARGIN + cols).contains(&x) {
+            line(
+                &mut framed,
+                Point::new(x, rows),
+                Point::new(x, rows + 5),
+                Scalar::all(255.0),
+                1,
+                LINE_8,
+                0,
+            )?;
+            label(&mut framed, frequency, Point::new(x - 12, rows + 18))?;
+        }
+        if (0..rows).contains(&y) {
+            line(
+                &mut framed,
+                Point::new(MARGIN - 5, y),
+                Point::new(MARGIN, y),
+                Scalar::all(255.0),
+                1,
+                LINE_8,
+                0,
+            )?;
+            label(&mut framed, frequency, Point::new(2, y + 4))?;
+        }
+    }
+    Ok(framed)
+}
+
+// Mean power |F|² over rings of equal frequency, in `bins` steps from 0 to
+// 0.5 cycles per pixel. Frequencies are normalised per axis, so rings are
+// ellipses on spectra that are not square.
+pub fn radial_power_spectrum(fft: &(Mat, Mat), bins: usize) -> Result<Vec<(f64, f64)>> {
+    let mut image_magnitude = new_mat();
+    magnitude(&fft.0, &fft.1, &mut image_magnitude)?;
+    let (rows, cols) = (image_magnitude.rows(), image_magnitude.cols());
+
+    let mut sums = vec![(0.0, 0usize); bins];
+    for i in 0..rows {
+        for j in 0..cols {
+            let u = (j - cols / 2) as f64 / cols as f64;
+            let v = (i - rows / 2) as f64 / rows as f64;
+            let bin = (u.hypot(v) / 0.5 * bins as f64) as usize;
+            if bin < bins {
+                let value = *image_magnitude.at_2d::<f32>(i, j)? as f64;
+                sums[bin].0 += value * value;
+                sums[bin].1 += 1;
+            }
+        }
+    }
+    Ok(sums
+        .into_iter()
+        .enumerate()
+        .filter(|(_, (_, count))| *count > 0)
+        .map(|(bin, (sum, count))| ((bin as f64 + 0.5) * 0.5 / bins as f64, sum / count as f64))
+        .collect())
+}
+
+// log10 power against frequency: a noise floor shows up as a flat tail, blur
+// as an early drop and periodic patterns as bumps.
+pub fn plot_radial_power(profile: &[(f64, f64)], width: i32, height: i32) -> Result<Mat> {
+    let mut plot = Mat::new_rows_cols_with_default(
+        height + MARGIN,
+        width + MARGIN,
+        CV_8UC3,
+        Scalar::all(0.0),
+    )?;
+    let powers: Vec<f64> = profile
+        .iter()
+        .map(|(_, power)| power.max(f64::MIN_POSITIVE).log10())
+        .collect();
+    let low = powers.iter().cloned().fold(f64::INFINITY, f64::min);
+    let high = powers.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    let range = (high - low).max(f64::EPSILON);
+
+    let point = |frequency: f64, power: f64| {
+        Point::new(
+            MARGIN + (frequency / 0.5 * width as f64) as i32,
+            ((high - power) / range * height as f64) as i32,
+        )
+    };
+    for (window, power) in profile.windows(2).zip(powers.windows(2)) {
+        line(
+            &mut plot,
+            point(window[0].0, power[0]),
+            point(window[1].0, power[1]),
+            Scalar::new(0.0, 255.0, 255.0, 0.0),
+            1,
+            LINE_8,
+            0,
+        )?;
+    }
+
+    for k in 0..=5 {
+        let frequency = k as f64 * 0.1;
+        label(
+            &mut plot,
+            frequency,
+            Point::new(
+                MARGIN + (frequency / 0.5 * width as f64) as i32 - 12,
+                height + 18,
+            ),
+        )?;
+    }
+    put_text(
+        &mut plot,
+        &format!("{:.1}", high),
+        Point::new(2, 12),
+        FONT_HERSHEY_SIMPLEX,
+        0.35,
+        Scalar::all(255.0),
+        1,
+        LINE_8,
+        false,
+    )?;
+    put_text(
+        &mut plot,
+        &format!("{:.1}", low),
+        Point::new(2, height),
+        FONT_HERSHEY_SIMPLEX,
+        0.35,
+        Scalar::all(255.0),
+        1,
+        LINE_8,
+        false,
+    )?;
+    Ok(plot)
+}
+
+fn label(image: &mut Mat, frequency: f64, at: Point) -> Result<()> {
+    put_text(
+        image,
+        &format!("{:.2}", frequency),
+        at,
+        FONT_HERSHEY_SIMPLEX,
+        0.35,
+        Scalar::all(255.0),
+        1,
+        LINE_8,
+        false,
+    )
+}
+
+fn to_8u(image: &Mat, scale: f64) -> Result<Mat> {
+    let image = mul_image(image, scale)?;
+    let mut clone = new_mat();
+    image.convert_to(&mut clone, CV_8U, 1.0, 0.0)?;
+    Ok(clone)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use opencv::core::{Vec3b, CV_32F};
+
+    use super::*;
+    use crate::fft_complex;
+
+    // A cosine of `fu` cycles per pixel along the columns plus one of `fv`
+    // along the rows.
+    fn cosines(rows: i32, cols: i32, fu: f64, fv: f64) -> Result<Mat> {
+        let mut image = Mat::zeros(rows, cols, CV_32F)?.to_mat()?;
+        for i in 0..rows {
+            for j in 0..cols {
+                let value = 0.5
+                    + 0.2 * (2.0 * PI * fu * j as f64).cos()
+                    + 0.2 * (2.0 * PI * fv * i as f64).cos();
+                *image.at_2d_mut::<f32>(i, j)? = value as f32;
+            }
+        }
+        Ok(image)
+    }
+
+    #[test]
+    fn radial_power_peaks_at_the_frequency_of_a_cosine() -> Result<()> {
+        let width = 0.5 / 32.0;
+        for &(rows, cols) in &[(64, 64), (64, 80)] {
+            let fft = fft_complex(&cosines(rows, cols, 0.25, 0.25)?)?;
+            let profile = radial_power_spectrum(&fft, 32)?;
+            // The first bin only holds the zero frequency.
+            assert!(profile[0].0 < width);
+            let (mut frequency, mut power) = profile[1];
+            for &(other, other_power) in &profile[2..] {
+                if other_power > power {
+                    frequency = other;
+                    power = other_power;
+                }
+            }
+            assert!(
+                (frequency - 0.25).abs() <= width,
+                "{}x{}: {}",
+                cols,
+                rows,
+                frequency
+            );
+            for &(other, other_power) in &profile[1..] {
+                if (other - 0.25).abs() > width {
+                    assert!(other_power < 1e-6 * power, "{}x{}: {}", cols, rows, other);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // The ticks for 0.25 cycles per pixel have to sit on the spikes of
+    // cosines of that frequency, also when the spectrum is not square.
+    #[test]
+    fn axis_ticks_sit_on_the_frequencies_they_name() -> Result<()> {
+        let (rows, cols) = (64, 80);
+        let fft = fft_complex(&cosines(rows, cols, 0.25, 0.25)?)?;
+        let spectrum = log_spectrum(&fft)?;
+        // The spikes off the zero frequency, on the centre row and column.
+        let (mut peak_col, mut peak_row) = (cols / 2 + 1, rows / 2 + 1);
+        for j in peak_col..cols {
+            if *spectrum.at_2d::<f32>(rows / 2, j)? > *spectrum.at_2d::<f32>(rows / 2, peak_col)? {
+                peak_col = j;
+            }
+        }
+        for i in peak_row..rows {
+            if *spectrum.at_2d::<f32>(i, cols / 2)? > *spectrum.at_2d::<f32>(peak_row, cols / 2)? {
+                peak_row = i;
+            }
+        }
+
+        let framed = annotate_axes(
+            &color_mapped(&log_magnitude(&fft)?, ColorMap::Viridis)?,
+            0.25,
+        )?;
+        let tick =
+            |y: i32, x: i32| -> Result<bool> { Ok(framed.at_2d::<Vec3b>(y, x)?.0 == [255; 3]) };
+        // Below the spectrum, clear of the labels.
+        assert!(tick(rows + 3, MARGIN + peak_col)?);
+        assert!(!tick(rows + 3, MARGIN + peak_col + 1)?);
+        // Left of the spectrum, clear of the labels.
+        assert!(tick(peak_row, MARGIN - 1)?);
+        assert!(!tick(peak_row + 1, MARGIN - 1)?);
+        Ok(())
+    }
+}
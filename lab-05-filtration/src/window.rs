@@ -0,0 +1,294 @@
+use std::f64::consts::PI;
+
+use opencv::{
+    core::{
+        add, dft, divide2, idft, mean, no_array, subtract, Mat, Vec2f, CV_32F, DFT_COMPLEX_OUTPUT,
+        DFT_REAL_OUTPUT, DFT_SCALE,
+    },
+    imgproc::{threshold, THRESH_TOZERO},
+    prelude::*,
+    Result,
+};
+
+use crate::{
+    fft_complex, fft_complex_unpadded,
+    filters::{apply_filter, FrequencyFilter},
+    ifft_complex, mul_add_image, mul_mat_image, new_mat,
+};
+
+#[derive(Debug, Clone, Copy)]
+pub enum Window {
+    Hann,
+    Hamming,
+    Blackman,
+    // Flat in the middle, cosine tapers over the outer `alpha / 2` on each side.
+    Tukey { alpha: f64 },
+    Kaiser { beta: f64 },
+}
+
+impl Window {
+    pub const NAMES: &'static [&'static str] = &["hann", "hamming", "blackman", "tukey", "kaiser"];
+
+    pub fn by_name(name: &str) -> Option<Window> {
+        match name {
+            "hann" => Some(Window::Hann),
+            "hamming" => Some(Window::Hamming),
+            "blackman" => Some(Window::Blackman),
+            "tukey" => Some(Window::Tukey { alpha: 0.5 }),
+            "kaiser" => Some(Window::Kaiser { beta: 6.0 }),
+            _ => None,
+        }
+    }
+
+    // Value at `x` in [0, 1] across the window.
+    fn at(self, x: f64) -> f64 {
+        match self {
+            Window::Hann => 0.5 - 0.5 * (2.0 * PI * x).cos(),
+            Window::Hamming => 0.54 - 0.46 * (2.0 * PI * x).cos(),
+            Window::Blackman => 0.42 - 0.5 * (2.0 * PI * x).cos() + 0.08 * (4.0 * PI * x).cos(),
+            Window::Tukey { alpha } => {
+                let edge = alpha / 2.0;
+                let x = x.min(1.0 - x);
+                if alpha <= 0.0 || x >= edge {
+                    1.0
+                } else {
+                    0.5 - 0.5 * (PI * x / edge).cos()
+                }
+            }
+            Window::Kaiser { beta } => {
+                let t = 2.0 * x - 1.0;
+                bessel_i0(beta * (1.0 - t * t).max(0.0).sqrt()) / bessel_i0(beta)
+            }
+        }
+    }
+
+    // Separable 2-D window w(x)·w(y) of the given size, CV_32F.
+    pub fn mat(self, rows: i32, cols: i32) -> Result<Mat> {
+        let mut window = Mat::zeros(rows, cols, CV_32F)?.to_mat()?;
+        let position = |i: i32, len: i32| {
+            if len > 1 {
+                i as f64 / (len - 1) as f64
+            } else {
+                0.5
+            }
+        };
+        for i in 0..rows {
+            let wy = self.at(position(i, rows));
+            for j in 0..cols {
+                *window.at_2d_mut::<f32>(i, j)? = (wy * self.at(position(j, cols))) as f32;
+            }
+        }
+        Ok(window)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Apodization {
+    None,
+    Window(Window),
+    PeriodicSmooth,
+}
+
+// The image before `fft_complex`: the mean is removed before windowing, so
+// the borders fall to zero and match the zero padding, or in the case of
+// Hamming (8% of the peak) and Kaiser (1 / I0(beta)) to a fraction of the
+// deviation from the mean.
+pub fn apodize(image: &Mat, window: Window) -> Result<Mat> {
+    let image_mean = mean(image, &no_array()?)?[0];
+    mul_mat_image(
+        &mul_add_image(image, 1.0, -image_mean)?,
+        &window.mat(image.rows(), image.cols())?,
+    )
+}
+
+// Moisan's decomposition u = p + s: `p` is periodic, so its spectrum has no
+// cross from the borders, and `s` is the smooth part that carries the
+// discontinuities between opposite edges. `p` is periodic at the size of the
+// image only, so it has to go through `fft_complex_unpadded`.
+pub fn periodic_smooth(image: &Mat) -> Result<(Mat, Mat)> {
+    let (rows, cols) = (image.rows(), image.cols());
+    let at = |i: i32, j: i32| -> Result<f64> { Ok(*image.at_2d::<f32>(i, j)? as f64) };
+
+    let mut boundary = Mat::zeros(rows, cols, CV_32F)?.to_mat()?;
+    for j in 0..cols {
+        let jump = at(rows - 1, j)? - at(0, j)?;
+        *boundary.at_2d_mut::<f32>(0, j)? += jump as f32;
+        *boundary.at_2d_mut::<f32>(rows - 1, j)? -= jump as f32;
+    }
+    for i in 0..rows {
+        let jump = at(i, cols - 1)? - at(i, 0)?;
+        *boundary.at_2d_mut::<f32>(i, 0)? += jump as f32;
+        *boundary.at_2d_mut::<f32>(i, cols - 1)? -= jump as f32;
+    }
+
+    // The discrete Laplacian of s equals the boundary term, which is a
+    // division in the frequency domain; the mean of s is set to zero.
+    let mut spectrum = new_mat();
+    dft(&boundary, &mut spectrum, DFT_COMPLEX_OUTPUT, 0)?;
+    for q in 0..rows {
+        for r in 0..cols {
+            let denominator = 2.0 * (2.0 * PI * q as f64 / rows as f64).cos()
+                + 2.0 * (2.0 * PI * r as f64 / cols as f64).cos()
+                - 4.0;
+            let value = spectrum.at_2d_mut::<Vec2f>(q, r)?;
+            if q == 0 && r == 0 {
+                *value = Vec2f::from([0.0, 0.0]);
+            } else {
+                value[0] /= denominator as f32;
+                value[1] /= denominator as f32;
+            }
+        }
+    }
+    let mut smooth = new_mat();
+    idft(&spectrum, &mut smooth, DFT_SCALE | DFT_REAL_OUTPUT, 0)?;
+
+    let mut periodic = new_mat();
+    subtract(image, &smooth, &mut periodic, &no_array()?, CV_32F)?;
+    Ok((periodic, smooth))
+}
+
+// Filters `image` with the leakage suppression undone afterwards. A window is
+// divided back out, down to `floor` to keep the borders from blowing up, and
+// the removed mean comes back with the filter's gain at the zero frequency.
+// The smooth part of the decomposition has almost no high frequencies, so it
+// is scaled by that same gain instead of being transformed.
+pub fn filter_apodized<F: FrequencyFilter + ?Sized>(
+    image: &Mat,
+    apodization: Apodization,
+    filter: &F,
+    floor: f64,
+) -> Result<Mat> {
+    let size = image.size()?;
+    match apodization {
+        Apodization::None => ifft_complex(&apply_filter(&fft_complex(image)?, filter)?, size),
+        Apodization::Window(window) => {
+            let image_mean = mean(image, &no_array()?)?[0];
+            let fft = fft_complex(&apodize(image, window)?)?;
+            let filtered = ifft_complex(&apply_filter(&fft, filter)?, size)?;
+
+            // max(w, floor) as floor + max(w - floor, 0).
+            let mut window_floor = new_mat();
+            threshold(
+                &mul_add_image(&window.mat(image.rows(), image.cols())?, 1.0, -floor)?,
+                &mut window_floor,
+                0.0,
+                0.0,
+                THRESH_TOZERO,
+            )?;
+            let window_floor = mul_add_image(&window_floor, 1.0, floor)?;
+            let mut unwindowed = new_mat();
+            divide2(&filtered, &window_floor, &mut unwindowed, 1.0, CV_32F)?;
+            mul_add_image(&unwindowed, 1.0, image_mean * filter.response(0.0, 0.0))
+        }
+        Apodization::PeriodicSmooth => {
+            let (periodic, smooth) = periodic_smooth(image)?;
+            let fft = fft_complex_unpadded(&periodic)?;
+            let filtered = ifft_complex(&apply_filter(&fft, filter)?, size)?;
+            let smooth = mul_add_image(&smooth, filter.response(0.0, 0.0), 0.0)?;
+            let mut result = new_mat();
+            add(&filtered, &smooth, &mut result, &no_array()?, CV_32F)?;
+            Ok(result)
+        }
+    }
+}
+
+// Modified Bessel function of the first kind, order zero, by its power series.
+fn bessel_i0(x: f64) -> f64 {
+    let (mut sum, mut term) = (1.0, 1.0);
+    let half = x / 2.0;
+    for k in 1..50 {
+        term *= (half / k as f64).powi(2);
+        sum += term;
+        if term < sum * 1e-12 {
+            break;
+        }
+    }
+    sum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bessel_i0_matches_reference_values() {
+        for &(x, expected) in &[
+            (0.0, 1.0),
+            (1.0, 1.266_065_877_752_008_4),
+            (6.0, 67.234_406_976_478),
+        ] {
+            assert!((bessel_i0(x) / expected - 1.0).abs() < 1e-10, "I0({})", x);
+        }
+    }
+
+    #[test]
+    fn windows_are_symmetric_with_the_expected_ends() {
+        let kaiser = Window::Kaiser { beta: 6.0 };
+        for &(window, end) in &[
+            (Window::Hann, 0.0),
+            (Window::Hamming, 0.08),
+            (Window::Blackman, 0.0),
+            (Window::Tukey { alpha: 0.5 }, 0.0),
+            (kaiser, 1.0 / bessel_i0(6.0)),
+        ] {
+            assert!((window.at(0.0) - end).abs() < 1e-9, "{:?}", window);
+            assert!((window.at(1.0) - end).abs() < 1e-9, "{:?}", window);
+            assert!((window.at(0.5) - 1.0).abs() < 1e-9, "{:?}", window);
+            for &x in &[0.1, 0.2, 0.3, 0.4] {
+                assert!((window.at(x) - window.at(1.0 - x)).abs() < 1e-9);
+            }
+        }
+        // Tukey is flat outside the tapers and Hann inside them.
+        let tukey = Window::Tukey { alpha: 0.5 };
+        assert_eq!(tukey.at(0.3), 1.0);
+        assert!((tukey.at(0.125) - 0.5).abs() < 1e-9);
+        assert_eq!(Window::Tukey { alpha: 0.0 }.at(0.0), 1.0);
+    }
+
+    #[test]
+    fn periodic_smooth_removes_the_jumps_between_opposite_edges() -> Result<()> {
+        let (rows, cols) = (12, 16);
+        let mut image = Mat::zeros(rows, cols, CV_32F)?.to_mat()?;
+        for i in 0..rows {
+            for j in 0..cols {
+                let value = j as f64 + 0.5 * i as f64 + 0.1 * ((i * j) % 5) as f64;
+                *image.at_2d_mut::<f32>(i, j)? = value as f32;
+            }
+        }
+        let (periodic, smooth) = periodic_smooth(&image)?;
+        let u = |i: i32, j: i32| -> Result<f64> { Ok(*image.at_2d::<f32>(i, j)? as f64) };
+        let p = |i: i32, j: i32| -> Result<f64> { Ok(*periodic.at_2d::<f32>(i, j)? as f64) };
+        let s = |i: i32, j: i32| -> Result<f64> { Ok(*smooth.at_2d::<f32>(i, j)? as f64) };
+
+        assert!(mean(&smooth, &no_array()?)?[0].abs() < 1e-4);
+        // The periodic Laplacian of p is the Laplacian of u taken only over
+        // neighbours inside the image, which defines the decomposition.
+        for i in 0..rows {
+            for j in 0..cols {
+                assert!((p(i, j)? + s(i, j)? - u(i, j)?).abs() < 1e-4);
+                let (mut periodic_laplacian, mut inner_laplacian) = (-4.0 * p(i, j)?, 0.0);
+                for &(di, dj) in &[(-1, 0), (1, 0), (0, -1), (0, 1)] {
+                    let (ni, nj) = (i + di, j + dj);
+                    periodic_laplacian += p(ni.rem_euclid(rows), nj.rem_euclid(cols))?;
+                    if (0..rows).contains(&ni) && (0..cols).contains(&nj) {
+                        inner_laplacian += u(ni, nj)? - u(i, j)?;
+                    }
+                }
+                assert!((periodic_laplacian - inner_laplacian).abs() < 1e-3);
+            }
+        }
+
+        let jumps = |at: &dyn Fn(i32, i32) -> Result<f64>| -> Result<f64> {
+            let mut total = 0.0;
+            for i in 0..rows {
+                total += (at(i, cols - 1)? - at(i, 0)?).abs();
+            }
+            for j in 0..cols {
+                total += (at(rows - 1, j)? - at(0, j)?).abs();
+            }
+            Ok(total)
+        };
+        assert!(jumps(&p)? < 0.2 * jumps(&u)?);
+        Ok(())
+    }
+}
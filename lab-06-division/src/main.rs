@@ -1,63 +1,135 @@
 use opencv::{
     core::{
-        abs, min_max_loc, no_array, subtract, Mat, Point, Scalar, BORDER_DEFAULT, CV_32F, CV_32FC1,
-        CV_32S, CV_64FC1, CV_8UC1, CV_8UC3,
+        abs, no_array, subtract, Mat, Point, Scalar, BORDER_DEFAULT, CV_32F, CV_32FC1, CV_32S,
+        CV_8UC1, CV_8UC3,
     },
     highgui,
-    imgcodecs::{self, IMREAD_GRAYSCALE},
     imgproc::{
-        cvt_color, dilate, draw_contours, filter_2d, find_contours,
-        morphology_default_border_value, threshold, watershed, CHAIN_APPROX_SIMPLE, COLOR_GRAY2BGR,
-        LINE_8, RETR_EXTERNAL, THRESH_BINARY,
+        circle, cvt_color, dilate, draw_contours, find_contours, morphology_default_border_value,
+        threshold, watershed, CHAIN_APPROX_SIMPLE, COLOR_GRAY2BGR, LINE_8, RETR_EXTERNAL,
+        THRESH_BINARY,
     },
     prelude::*,
     types::VectorOfMat,
     Result,
 };
 
-fn show(name: &str, mat: &Mat) -> Result<()> {
-    highgui::named_window(name, 0)?;
-    highgui::imshow(name, &{
-        let mut clone = mat.clone();
-        mat.convert_to(&mut clone, CV_8UC1, 255.0, 0.0)?;
-        clone
-    })?;
-
-    Ok(())
-}
+#[path = "../../shared/cli.rs"]
+mod cli;
+#[path = "../../shared/export.rs"]
+mod export;
+#[path = "../../shared/fft.rs"]
+mod fft;
+#[path = "../../shared/filters.rs"]
+mod filters;
+#[path = "../../shared/homomorphic.rs"]
+mod homomorphic;
+#[path = "../../shared/image_io.rs"]
+mod image_io;
+#[path = "../../shared/kernel.rs"]
+mod kernel;
+#[path = "../../shared/metrics.rs"]
+mod metrics;
+#[path = "../../shared/normalization.rs"]
+mod normalization;
+#[path = "../../shared/pipeline.rs"]
+mod pipeline;
+mod scale_space;
+mod texture;
+use cli::{arg, arg_choice, arg_value, display_normalization, save};
+use filters::HighEmphasis;
+use homomorphic::{homomorphic, Homomorphic};
+use image_io::load_float;
+use kernel::{Border, Kernel};
+use normalization::normalize;
+use pipeline::Pipeline;
+use scale_space::{detect_blobs, geometric_sigmas, zero_crossings, Operator, ScaleSpace};
+use texture::{region_features, Execution, GaborBank};
 
 fn main() -> Result<()> {
-    let image_file = {
-        let img = imgcodecs::imread("./src.png", IMREAD_GRAYSCALE)?;
-        let mut clone = new_mat(CV_32F);
-        img.convert_to(&mut clone, CV_32F, 1.0 / 255.0, 0.0)?;
-        clone
-    };
+    let image_file = load_float(
+        &arg("image").unwrap_or_else(|| "./src.png".to_string()),
+        1.0,
+    )?;
+
+    let mut pipeline = Pipeline::new(1.0);
+    pipeline.stage("image file", &image_file)?;
 
-    show("image file", &image_file)?;
+    let kernel = match arg("kernel") {
+        Some(path) => Kernel::load(&path)?,
+        None => Kernel::laplacian(),
+    };
+    let border =
+        arg_choice("border", Border::NAMES, Border::by_name)?.unwrap_or(Border::Reflect101);
 
-    let kernel = kernel()?;
+    let image_laplacian = kernel.convolve(&image_file, border)?;
+    pipeline.stage("image_laplacian", &correction(&image_laplacian)?)?;
 
-    let mut image_laplacian = new_mat(CV_32F);
-    filter_2d(
-        &image_file,
-        &mut image_laplacian,
+    // `scale_space=log|dog` over `levels=` scales from `sigma_min=` to
+    // `sigma_max=`; blobs are only found on the scales in between.
+    let sigmas = geometric_sigmas(
+        arg_value("sigma_min")?.unwrap_or(2.0),
+        arg_value("sigma_max")?.unwrap_or(32.0),
+        arg_value("levels")?.unwrap_or(12),
+    );
+    let operator = arg_choice("scale_space", Operator::NAMES, Operator::by_name)?
+        .unwrap_or(Operator::LaplacianOfGaussian);
+    let scale_space = ScaleSpace::new(&image_file, &sigmas, operator)?;
+    let image_zero_crossings = convert(
+        &zero_crossings(&scale_space.responses[0], 0.01)?,
         CV_32F,
-        &kernel,
-        Point::new(-1, -1),
-        0.0,
-        BORDER_DEFAULT,
+        1.0 / 255.0,
     )?;
-    highgui::named_window("image_laplacian", 0)?;
-    highgui::imshow("image_laplacian", &convert(&image_laplacian, CV_32F, 1.0)?)?;
+    pipeline.stage("image_zero_crossings", &image_zero_crossings)?;
+    save("zero_crossings", &image_zero_crossings, 1.0)?;
 
-    let image_cvt = convert(&image_file, CV_8UC1, 255.0)?;
+    let blobs = detect_blobs(&scale_space, arg_value("min_strength")?.unwrap_or(0.3))?;
+    let mut image_blobs = convert(
+        &convert_color(&convert(&image_file, CV_8UC1, 255.0)?, COLOR_GRAY2BGR)?,
+        CV_8UC3,
+        1.0,
+    )?;
+    for blob in &blobs {
+        println!(
+            "blob at ({}, {}): sigma {:.1}, radius {:.1}, response {:.3}",
+            blob.x, blob.y, blob.sigma, blob.radius, blob.response
+        );
+        circle(
+            &mut image_blobs,
+            Point::new(blob.x, blob.y),
+            blob.radius.round() as i32,
+            Scalar::new(0.0, 0.0, 255.0, 255.0),
+            1,
+            LINE_8,
+            0,
+        )?;
+    }
+    pipeline.stage("image_blobs", &convert(&image_blobs, CV_32F, 1.0 / 255.0)?)?;
+
+    // With `homomorphic=gaussian|butterworth` uneven lighting is evened out
+    // before the fixed threshold below.
+    let image_even = match arg_choice("homomorphic", HighEmphasis::NAMES, HighEmphasis::by_name)? {
+        Some(filter) => {
+            let image_corrected = homomorphic(
+                &image_file,
+                &Homomorphic {
+                    filter,
+                    low: 0.5,
+                    high: 1.5,
+                },
+            )?;
+            pipeline.stage("image_homomorphic", &image_corrected)?;
+            image_corrected
+        }
+        None => image_file.clone(),
+    };
+    let image_cvt = convert(&image_even, CV_8UC1, 255.0)?;
 
     let mut bw_thr = new_mat(CV_32F);
     threshold(&image_cvt, &mut bw_thr, 100.0, 255.0, THRESH_BINARY)?;
 
     let peaks = invert(&bw_thr)?;
-    highgui::imshow("Peaks", &peaks)?;
+    pipeline.stage("Peaks", &peaks)?;
 
     let mut background_markers = new_mat(CV_32F);
     let kernel1 = Mat::ones(5, 5, CV_8UC1)?;
@@ -71,7 +143,7 @@ fn main() -> Result<()> {
         morphology_default_border_value()?,
     )?;
 
-    highgui::imshow("background_markers", &background_markers)?;
+    pipeline.stage("background_markers", &background_markers)?;
 
     // Searching for contours on peaks Map
     let mut markers = {
@@ -109,7 +181,7 @@ fn main() -> Result<()> {
         markers
     };
     let markers_8u = convert(&markers, CV_8UC1, 20.0)?;
-    highgui::imshow("Markers", &markers_8u)?;
+    pipeline.stage("Markers", &convert(&markers_8u, CV_32F, 1.0 / 255.0)?)?;
 
     let image_result = convert(
         &convert_color(&image_laplacian, COLOR_GRAY2BGR)?,
@@ -128,24 +200,27 @@ fn main() -> Result<()> {
             }
         }
     }
-    highgui::imshow("watershed", &mark)?;
-    highgui::wait_key(-1)?;
-
-    Ok(())
-}
+    pipeline.stage("watershed", &mark)?;
+    save("watershed", &mark, 1.0)?;
 
-fn kernel() -> Result<Mat> {
-    let mut mat = Mat::zeros(3, 3, CV_64FC1)?.to_mat()?;
-
-    let data = [[1.0, 1.0, 1.0], [1.0, -8.0, 1.0], [1.0, 1.0, 1.0]];
+    let bank = GaborBank::new(&[4.0, 8.0, 16.0], 4);
+    let execution =
+        arg_choice("gabor", Execution::NAMES, Execution::by_name)?.unwrap_or(Execution::Spatial);
+    let energies = bank.energies(&image_file, execution)?;
+    for region in region_features(&energies, &markers)? {
+        println!(
+            "region {} ({} px): mean {:.4?}, variance {:.4?}",
+            region.label, region.area, region.mean, region.variance
+        );
+    }
+    pipeline.report()?;
 
-    for i in 0..3 {
-        for j in 0..3 {
-            *mat.at_2d_mut(i, j)? = data[i as usize][j as usize];
-        }
+    if let Some(path) = arg("export") {
+        pipeline.export(&path)?;
     }
+    highgui::wait_key(-1)?;
 
-    Ok(mat)
+    Ok(())
 }
 
 fn invert(mat: &Mat) -> Result<Mat> {
@@ -178,29 +253,27 @@ fn apply<F: Fn(&Mat, &mut Mat) -> Result<()>>(src: &Mat, f: F) -> Result<Mat> {
     Ok(clone)
 }
 
+// For the shared modules, which cannot assume whether the getter is fallible.
+fn channels(image: &Mat) -> Result<i32> {
+    Ok(image.channels())
+}
+
+fn depth(image: &Mat) -> Result<i32> {
+    Ok(image.depth())
+}
+
 fn new_mat(typ: i32) -> Mat {
     Mat::zeros(0, 0, typ).unwrap().to_mat().unwrap()
 }
 
+fn mul_mat_image(image: &Mat, mul: &Mat) -> Result<Mat> {
+    image.mul(mul, 1.0)?.to_mat()
+}
+
 fn abs_image(image: &Mat) -> Result<Mat> {
     abs(image)?.to_mat()
 }
 
 fn correction(image: &Mat) -> Result<Mat> {
-    let mut min = 0.0;
-    let mut max = 0.0;
-    min_max_loc(
-        image,
-        Some(&mut min),
-        Some(&mut max),
-        None,
-        None,
-        &no_array(),
-    )?;
-    let mut clone = image.clone();
-    image.convert_to(&mut clone, CV_32F, 1.0, -min)?;
-
-    let mut clone2 = clone.clone();
-    clone.convert_to(&mut clone2, CV_32F, 1.0 / (max - min), 0.0)?;
-    Ok(clone2)
+    Ok(normalize(image, display_normalization()?, 1.0)?.0)
 }
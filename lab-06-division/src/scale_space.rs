@@ -0,0 +1,278 @@
+use opencv::{
+    core::{no_array, subtract, Mat, Size, BORDER_DEFAULT, CV_32F, CV_8UC1},
+    imgproc::{gaussian_blur, laplacian},
+    prelude::*,
+    Result,
+};
+
+use crate::{convert, new_mat};
+
+// Responses are scale-normalised and sign-flipped, so that bright blobs
+// on a dark background give positive peaks at every scale.
+pub struct ScaleSpace {
+    pub sigmas: Vec<f64>,
+    pub responses: Vec<Mat>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Blob {
+    pub x: i32,
+    pub y: i32,
+    pub sigma: f64,
+    pub radius: f64,
+    pub response: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Operator {
+    LaplacianOfGaussian,
+    // Blurs at σ and k·σ.
+    DifferenceOfGaussians { k: f64 },
+}
+
+impl Operator {
+    pub const NAMES: &'static [&'static str] = &["log", "dog"];
+
+    pub fn by_name(name: &str) -> Option<Operator> {
+        match name {
+            "log" => Some(Operator::LaplacianOfGaussian),
+            "dog" => Some(Operator::DifferenceOfGaussians { k: 1.6 }),
+            _ => None,
+        }
+    }
+}
+
+impl ScaleSpace {
+    pub fn new(image: &Mat, sigmas: &[f64], operator: Operator) -> Result<ScaleSpace> {
+        match operator {
+            Operator::LaplacianOfGaussian => ScaleSpace::log(image, sigmas),
+            Operator::DifferenceOfGaussians { k } => ScaleSpace::dog(image, sigmas, k),
+        }
+    }
+
+    // With the 4-neighbour ∇², whose scale matches the continuous operator.
+    pub fn log(image: &Mat, sigmas: &[f64]) -> Result<ScaleSpace> {
+        let mut responses = Vec::with_capacity(sigmas.len());
+        for &sigma in sigmas {
+            let mut image_laplacian = new_mat(CV_32F);
+            laplacian(
+                &blurred(image, sigma)?,
+                &mut image_laplacian,
+                CV_32F,
+                1,
+                1.0,
+                0.0,
+                BORDER_DEFAULT,
+            )?;
+            responses.push(convert(&image_laplacian, CV_32F, -sigma * sigma)?);
+        }
+        Ok(ScaleSpace {
+            sigmas: sigmas.to_vec(),
+            responses,
+        })
+    }
+
+    // G(k·σ) - G(σ) ≈ (k - 1)·σ²·∇²G, so dividing by (k - 1) gives the same
+    // normalisation as `log`.
+    pub fn dog(image: &Mat, sigmas: &[f64], k: f64) -> Result<ScaleSpace> {
+        let mut responses = Vec::with_capacity(sigmas.len());
+        for &sigma in sigmas {
+            let mut diff = new_mat(CV_32F);
+            subtract(
+                &blurred(image, sigma)?,
+                &blurred(image, k * sigma)?,
+                &mut diff,
+                &no_array(),
+                CV_32F,
+            )?;
+            responses.push(convert(&diff, CV_32F, 1.0 / (k - 1.0))?);
+        }
+        Ok(ScaleSpace {
+            sigmas: sigmas.to_vec(),
+            responses,
+        })
+    }
+}
+
+pub fn geometric_sigmas(min: f64, max: f64, count: usize) -> Vec<f64> {
+    if count < 2 {
+        return vec![min];
+    }
+    let ratio = (max / min).powf(1.0 / (count - 1) as f64);
+    (0..count).map(|i| min * ratio.powi(i as i32)).collect()
+}
+
+pub fn zero_crossings(response: &Mat, min_slope: f32) -> Result<Mat> {
+    let mut result = Mat::zeros(response.rows(), response.cols(), CV_8UC1)?.to_mat()?;
+    for x in 0..response.rows() - 1 {
+        for y in 0..response.cols() - 1 {
+            let value = *response.at_2d::<f32>(x, y)?;
+            let right = *response.at_2d::<f32>(x, y + 1)?;
+            let down = *response.at_2d::<f32>(x + 1, y)?;
+
+            let crosses = |other: f32| value * other < 0.0 && (value - other).abs() >= min_slope;
+            if crosses(right) || crosses(down) {
+                *result.at_2d_mut::<u8>(x, y)? = 255;
+            }
+        }
+    }
+    Ok(result)
+}
+
+// A blob is a local maximum of the response magnitude over its 3×3×3
+// neighbourhood in (x, y, σ). The first and last scales only serve as
+// neighbours, since a maximum there may continue beyond the sampled range, so
+// at least three scales are needed. `min_strength` is relative to the
+// strongest response in the whole scale space.
+pub fn detect_blobs(space: &ScaleSpace, min_strength: f32) -> Result<Vec<Blob>> {
+    let magnitudes = space
+        .responses
+        .iter()
+        .map(|response| {
+            let (rows, cols) = (response.rows(), response.cols());
+            let mut values = Vec::with_capacity((rows * cols) as usize);
+            for row in 0..rows {
+                for col in 0..cols {
+                    values.push(response.at_2d::<f32>(row, col)?.abs());
+                }
+            }
+            Ok(values)
+        })
+        .collect::<Result<Vec<Vec<f32>>>>()?;
+
+    let (rows, cols) = match space.responses.first() {
+        Some(response) => (response.rows(), response.cols()),
+        None => return Ok(vec![]),
+    };
+    let strongest = magnitudes
+        .iter()
+        .flat_map(|values| values.iter())
+        .fold(0.0f32, |acc, &value| acc.max(value));
+    let threshold = strongest * min_strength;
+
+    let mut blobs = vec![];
+    for s in 1..magnitudes.len().saturating_sub(1) {
+        let values = &magnitudes[s];
+        for row in 0..rows {
+            for col in 0..cols {
+                let value = values[(row * cols + col) as usize];
+                if value <= threshold || value <= 0.0 {
+                    continue;
+                }
+                if !is_local_max(&magnitudes, s, row, col, rows, cols) {
+                    continue;
+                }
+                blobs.push(Blob {
+                    x: col,
+                    y: row,
+                    sigma: space.sigmas[s],
+                    radius: space.sigmas[s] * 2f64.sqrt(),
+                    response: *space.responses[s].at_2d::<f32>(row, col)?,
+                });
+            }
+        }
+    }
+    Ok(blobs)
+}
+
+fn is_local_max(
+    magnitudes: &[Vec<f32>],
+    s: usize,
+    row: i32,
+    col: i32,
+    rows: i32,
+    cols: i32,
+) -> bool {
+    let value = magnitudes[s][(row * cols + col) as usize];
+    for values in &magnitudes[s - 1..=s + 1] {
+        for dr in -1..=1 {
+            for dc in -1..=1 {
+                let (nr, nc) = (row + dr, col + dc);
+                if nr < 0 || nc < 0 || nr >= rows || nc >= cols {
+                    continue;
+                }
+                if values[(nr * cols + nc) as usize] > value {
+                    return false;
+                }
+            }
+        }
+    }
+    true
+}
+
+fn blurred(image: &Mat, sigma: f64) -> Result<Mat> {
+    let mut clone = new_mat(CV_32F);
+    gaussian_blur(
+        image,
+        &mut clone,
+        Size::new(0, 0),
+        sigma,
+        sigma,
+        BORDER_DEFAULT,
+    )?;
+    Ok(clone)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image<F: Fn(i32, i32) -> f32>(rows: i32, cols: i32, value: F) -> Result<Mat> {
+        let mut image = Mat::zeros(rows, cols, CV_32F)?.to_mat()?;
+        for i in 0..rows {
+            for j in 0..cols {
+                *image.at_2d_mut::<f32>(i, j)? = value(i, j);
+            }
+        }
+        Ok(image)
+    }
+
+    // The scale-normalised LoG of a disc of radius r peaks at σ = r/√2.
+    #[test]
+    fn a_disc_is_detected_at_its_scale() -> Result<()> {
+        let radius = 8.0;
+        let disc = image(64, 64, |i, j| {
+            let (y, x) = (i as f32 - 31.5, j as f32 - 31.5);
+            if x * x + y * y <= radius * radius {
+                1.0
+            } else {
+                0.0
+            }
+        })?;
+        let space = ScaleSpace::log(&disc, &geometric_sigmas(2.0, 12.0, 11))?;
+        let blob = detect_blobs(&space, 0.5)?
+            .into_iter()
+            .max_by(|a, b| a.response.partial_cmp(&b.response).unwrap())
+            .unwrap();
+        assert!((blob.x as f32 - 31.5).abs() <= 1.0 && (blob.y as f32 - 31.5).abs() <= 1.0);
+        let expected = radius as f64 / 2f64.sqrt();
+        assert!((blob.sigma - expected).abs() < 0.1 * expected);
+        assert!((blob.radius - radius as f64).abs() < 0.1 * radius as f64);
+
+        // Without a scale on either side nothing counts as a maximum.
+        let space = ScaleSpace::log(&disc, &[4.0, 6.0])?;
+        assert!(detect_blobs(&space, 0.5)?.is_empty());
+        Ok(())
+    }
+
+    // The response of a step between columns 15 and 16 is odd about the
+    // edge, so it changes sign there and nowhere else.
+    #[test]
+    fn a_step_edge_gives_zero_crossings_along_it() -> Result<()> {
+        let step = image(32, 32, |_, j| if j < 16 { 0.0 } else { 1.0 })?;
+        for &operator in &[
+            Operator::LaplacianOfGaussian,
+            Operator::DifferenceOfGaussians { k: 1.6 },
+        ] {
+            let space = ScaleSpace::new(&step, &[2.0], operator)?;
+            let crossings = zero_crossings(&space.responses[0], 0.001)?;
+            for i in 0..31 {
+                for j in 0..31 {
+                    let expected = if j == 15 { 255 } else { 0 };
+                    assert_eq!(*crossings.at_2d::<u8>(i, j)?, expected, "{:?}", operator);
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,231 @@
+use std::{collections::BTreeMap, f64::consts::PI};
+
+use opencv::{
+    core::{add, no_array, Mat, Size, CV_32F},
+    imgproc::get_gabor_kernel,
+    prelude::*,
+    Result,
+};
+
+use crate::{
+    kernel::{Border, Kernel, Method},
+    new_mat,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Execution {
+    Spatial,
+    // Through the DFT, faster for the large kernels of coarse scales.
+    Frequency,
+}
+
+impl Execution {
+    pub const NAMES: &'static [&'static str] = &["spatial", "frequency"];
+
+    pub fn by_name(name: &str) -> Option<Execution> {
+        match name {
+            "spatial" => Some(Execution::Spatial),
+            "frequency" => Some(Execution::Frequency),
+            _ => None,
+        }
+    }
+}
+
+// `orientation` is in degrees; `sigma` is the width of the Gaussian envelope
+// and `gamma` its aspect ratio.
+#[derive(Debug, Clone, Copy)]
+pub struct GaborKernel {
+    pub wavelength: f64,
+    pub orientation: f64,
+    pub sigma: f64,
+    pub gamma: f64,
+}
+
+impl GaborKernel {
+    // Even (cosine) or odd (sine) kernel of the quadrature pair, wide enough
+    // for three standard deviations of the envelope along its long axis,
+    // sigma / gamma. With the sigma and gamma of `GaborBank` that is 109×109
+    // at a wavelength of 16, which is what `Execution::Frequency` is for.
+    fn kernel(&self, odd: bool) -> Result<Kernel> {
+        let size = 2 * (3.0 * self.sigma / self.gamma.min(1.0)).ceil() as i32 + 1;
+        Kernel::from_mat(&get_gabor_kernel(
+            Size::new(size, size),
+            self.sigma,
+            self.orientation.to_radians(),
+            self.wavelength,
+            self.gamma,
+            if odd { PI / 2.0 } else { 0.0 },
+            CV_32F,
+        )?)
+    }
+}
+
+pub struct GaborBank {
+    pub filters: Vec<GaborKernel>,
+}
+
+impl GaborBank {
+    // Every wavelength at `orientations` evenly spaced angles in [0, 180), with
+    // a one-octave bandwidth (sigma ≈ 0.56 · wavelength).
+    pub fn new(wavelengths: &[f64], orientations: usize) -> GaborBank {
+        let mut filters = vec![];
+        for &wavelength in wavelengths {
+            for i in 0..orientations {
+                filters.push(GaborKernel {
+                    wavelength,
+                    orientation: 180.0 * i as f64 / orientations as f64,
+                    sigma: 0.56 * wavelength,
+                    gamma: 0.5,
+                });
+            }
+        }
+        GaborBank { filters }
+    }
+
+    // One CV_32F map per filter with the local energy even² + odd², which
+    // does not depend on the phase of the texture.
+    pub fn energies(&self, image: &Mat, execution: Execution) -> Result<Vec<Mat>> {
+        let mut energies = vec![];
+        for filter in &self.filters {
+            let even = correlate(image, &filter.kernel(false)?, execution)?;
+            let odd = correlate(image, &filter.kernel(true)?, execution)?;
+            let mut energy = new_mat(CV_32F);
+            add(
+                &even.mul(&even, 1.0)?.to_mat()?,
+                &odd.mul(&odd, 1.0)?.to_mat()?,
+                &mut energy,
+                &no_array(),
+                CV_32F,
+            )?;
+            energies.push(energy);
+        }
+        Ok(energies)
+    }
+}
+
+// Mean and variance of every energy map over one region.
+#[derive(Debug, Clone)]
+pub struct RegionFeatures {
+    pub label: i32,
+    pub area: usize,
+    pub mean: Vec<f64>,
+    pub variance: Vec<f64>,
+}
+
+// `markers` is a CV_32S label image such as the output of `watershed`;
+// boundaries (-1) and unlabelled pixels (0) are skipped.
+pub fn region_features(energies: &[Mat], markers: &Mat) -> Result<Vec<RegionFeatures>> {
+    let mut sums: BTreeMap<i32, (usize, Vec<f64>, Vec<f64>)> = BTreeMap::new();
+    for row in 0..markers.rows() {
+        for col in 0..markers.cols() {
+            let label = *markers.at_2d::<i32>(row, col)?;
+            if label <= 0 {
+                continue;
+            }
+            let entry = sums
+                .entry(label)
+                .or_insert_with(|| (0, vec![0.0; energies.len()], vec![0.0; energies.len()]));
+            entry.0 += 1;
+            for (i, energy) in energies.iter().enumerate() {
+                let value = *energy.at_2d::<f32>(row, col)? as f64;
+                entry.1[i] += value;
+                entry.2[i] += value * value;
+            }
+        }
+    }
+
+    Ok(sums
+        .into_iter()
+        .map(|(label, (area, sum, sum_sq))| {
+            let mean: Vec<f64> = sum.iter().map(|s| s / area as f64).collect();
+            let variance = sum_sq
+                .iter()
+                .zip(&mean)
+                .map(|(s, m)| (s / area as f64 - m * m).max(0.0))
+                .collect();
+            RegionFeatures {
+                label,
+                area,
+                mean,
+                variance,
+            }
+        })
+        .collect())
+}
+
+// Like `filter_2d` with the kernels of `get_gabor_kernel`; convolving would
+// only negate the odd response, not change the energy. Both paths reflect
+// the image at its borders, so they give the same result.
+fn correlate(image: &Mat, kernel: &Kernel, execution: Execution) -> Result<Mat> {
+    match execution {
+        Execution::Spatial => kernel.correlate_by(image, Border::Reflect, Method::Direct),
+        Execution::Frequency => kernel.correlate_by(image, Border::Reflect, Method::Fourier),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // cos(2π·(x·cos θ + y·sin θ) / wavelength), x along the columns.
+    fn grating(size: i32, wavelength: f64, angle: f64) -> Result<Mat> {
+        let (sin, cos) = angle.to_radians().sin_cos();
+        let mut image = Mat::zeros(size, size, CV_32F)?.to_mat()?;
+        for i in 0..size {
+            for j in 0..size {
+                let phase = 2.0 * PI * (j as f64 * cos + i as f64 * sin) / wavelength;
+                *image.at_2d_mut::<f32>(i, j)? = phase.cos() as f32;
+            }
+        }
+        Ok(image)
+    }
+
+    // Mean energy away from the borders, where the reflection breaks the
+    // grating.
+    fn inner_mean(energy: &Mat) -> Result<f64> {
+        let margin = energy.rows() / 4;
+        let mut sum = 0.0;
+        let mut count = 0;
+        for i in margin..energy.rows() - margin {
+            for j in margin..energy.cols() - margin {
+                sum += *energy.at_2d::<f32>(i, j)? as f64;
+                count += 1;
+            }
+        }
+        Ok(sum / count as f64)
+    }
+
+    #[test]
+    fn spatial_and_frequency_energies_agree() -> Result<()> {
+        let image = grating(48, 6.0, 30.0)?;
+        let bank = GaborBank::new(&[4.0, 8.0], 4);
+        let spatial = bank.energies(&image, Execution::Spatial)?;
+        let frequency = bank.energies(&image, Execution::Frequency)?;
+        for (spatial, frequency) in spatial.iter().zip(&frequency) {
+            let spatial = spatial.data_typed::<f32>()?;
+            let largest = spatial.iter().fold(0.0f32, |max, &value| max.max(value));
+            for (s, f) in spatial.iter().zip(frequency.data_typed::<f32>()?) {
+                assert!((s - f).abs() <= 1e-3 * largest, "{} {}", s, f);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn the_strongest_response_has_the_grating_orientation() -> Result<()> {
+        let bank = GaborBank::new(&[8.0], 4);
+        for (index, &angle) in [0.0, 45.0, 90.0, 135.0].iter().enumerate() {
+            let image = grating(64, 8.0, angle)?;
+            let means = bank
+                .energies(&image, Execution::Frequency)?
+                .iter()
+                .map(inner_mean)
+                .collect::<Result<Vec<_>>>()?;
+            let strongest = (0..means.len())
+                .max_by(|&a, &b| means[a].partial_cmp(&means[b]).unwrap())
+                .unwrap();
+            assert_eq!(strongest, index, "{}°: {:?}", angle, means);
+        }
+        Ok(())
+    }
+}
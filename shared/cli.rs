@@ -0,0 +1,109 @@
+use opencv::{
+    core::{self, Mat, CV_8U},
+    highgui,
+    prelude::*,
+    Error, Result,
+};
+
+use crate::{
+    image_io::{save_16u, save_float},
+    normalization::{normalize, Normalization},
+};
+
+// Options are given as `name=value` anywhere on the command line.
+pub fn arg(name: &str) -> Option<String> {
+    let prefix = format!("{}=", name);
+    std::env::args().find_map(|arg| arg.strip_prefix(&prefix).map(String::from))
+}
+
+// Looks `name=` up with `by_name`; a value that is not one of `names` is an
+// error rather than a silent fallback to the default.
+pub fn arg_choice<T>(
+    name: &str,
+    names: &[&str],
+    by_name: fn(&str) -> Option<T>,
+) -> Result<Option<T>> {
+    match arg(name) {
+        Some(value) => by_name(&value).map(Some).ok_or_else(|| {
+            Error::new(
+                core::StsBadArg,
+                format!("{}={} is not one of {}", name, value, names.join(", ")),
+            )
+        }),
+        None => Ok(None),
+    }
+}
+
+// Parses `name=`; a value that does not parse is an error as well.
+pub fn arg_value<T: std::str::FromStr>(name: &str) -> Result<Option<T>> {
+    match arg(name) {
+        Some(value) => value.parse().map(Some).map_err(|_| {
+            Error::new(
+                core::StsBadArg,
+                format!("{}={} is not a valid number", name, value),
+            )
+        }),
+        None => Ok(None),
+    }
+}
+
+// How corrections and spectra are stretched for display, `display=` on the
+// command line. Percentiles by default, so that a single hot pixel cannot
+// flatten everything else to black; `display=fixed` maps the range from
+// `display_min=` to `display_max=`, 0 to 1 unless given.
+pub fn display_normalization() -> Result<Normalization> {
+    let default = Normalization::Percentile {
+        low: 1.0,
+        high: 99.0,
+    };
+    let normalization =
+        arg_choice("display", Normalization::NAMES, Normalization::by_name)?.unwrap_or(default);
+    Ok(match normalization {
+        Normalization::Fixed { min, max } => Normalization::Fixed {
+            min: arg_value("display_min")?.unwrap_or(min),
+            max: arg_value("display_max")?.unwrap_or(max),
+        },
+        other => other,
+    })
+}
+
+// Writes `image` into the `save=` directory, if one was given: as a float
+// TIFF by default, or with `format=png16` as a lossless 16-bit PNG of
+// [0, `peak`].
+pub fn save(name: &str, image: &Mat, peak: f64) -> Result<()> {
+    let dir = match arg("save") {
+        Some(dir) => dir,
+        None => return Ok(()),
+    };
+    match arg("format").as_deref() {
+        None | Some("float") => save_float(&format!("{}/{}.tiff", dir, name), image),
+        Some("png16") => save_16u(&format!("{}/{}.png", dir, name), image, peak),
+        Some(other) => Err(Error::new(
+            core::StsBadArg,
+            format!("format={} is not one of float, png16", other),
+        )),
+    }
+}
+
+// Displays [0, `peak`] as 8 bits in a window called `name`, clipping
+// everything outside.
+pub fn show(name: &str, mat: &Mat, peak: f64) -> Result<()> {
+    let (image, mapping) = normalize(
+        mat,
+        Normalization::Fixed {
+            min: 0.0,
+            max: peak,
+        },
+        255.0,
+    )?;
+    println!("{}: {}", name, mapping);
+
+    highgui::named_window(name, 0)?;
+    highgui::imshow(name, &{
+        let mut clone = image.clone();
+        image.convert_to(&mut clone, CV_8U, 1.0, 0.0)?;
+        clone
+    })?;
+
+    Ok(())
+}
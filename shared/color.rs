@@ -0,0 +1,194 @@
+use opencv::{
+    core::{merge, split, Mat, CV_32F},
+    imgcodecs::{IMREAD_ANYDEPTH, IMREAD_COLOR},
+    imgproc::{
+        cvt_color, threshold, COLOR_BGR2Lab, COLOR_BGR2YCrCb, COLOR_Lab2BGR, COLOR_YCrCb2BGR,
+        COLOR_BGR2HSV, COLOR_HSV2BGR, THRESH_TOZERO, THRESH_TRUNC,
+    },
+    prelude::*,
+    types::VectorOfMat,
+    Result,
+};
+
+use crate::image_io::load_float_with;
+
+// Same as `image_io::load_float`, but keeps three BGR channels.
+pub fn load_color_float(path: &str, peak: f64) -> Result<Mat> {
+    load_float_with(path, IMREAD_COLOR | IMREAD_ANYDEPTH, peak)
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum LuminanceSpace {
+    YCrCb,
+    Lab,
+    Hsv,
+}
+
+impl LuminanceSpace {
+    pub const NAMES: &'static [&'static str] = &["ycrcb", "lab", "hsv"];
+
+    pub fn by_name(name: &str) -> Option<LuminanceSpace> {
+        match name {
+            "ycrcb" => Some(LuminanceSpace::YCrCb),
+            "lab" => Some(LuminanceSpace::Lab),
+            "hsv" => Some(LuminanceSpace::Hsv),
+            _ => None,
+        }
+    }
+
+    fn codes(self) -> (i32, i32) {
+        match self {
+            LuminanceSpace::YCrCb => (COLOR_BGR2YCrCb, COLOR_YCrCb2BGR),
+            LuminanceSpace::Lab => (COLOR_BGR2Lab, COLOR_Lab2BGR),
+            LuminanceSpace::Hsv => (COLOR_BGR2HSV, COLOR_HSV2BGR),
+        }
+    }
+
+    fn luminance_channel(self) -> usize {
+        match self {
+            LuminanceSpace::YCrCb | LuminanceSpace::Lab => 0,
+            LuminanceSpace::Hsv => 2,
+        }
+    }
+
+    // Float conversions expect BGR in [0, 1]; L of Lab comes out in [0, 100].
+    fn luminance_range(self) -> f64 {
+        match self {
+            LuminanceSpace::Lab => 100.0,
+            LuminanceSpace::YCrCb | LuminanceSpace::Hsv => 1.0,
+        }
+    }
+}
+
+// A colour image split into its luminance (CV_32F, 0..`peak`, like the image
+// it came from) and the untouched chroma channels of the chosen space.
+pub struct Luminance {
+    pub space: LuminanceSpace,
+    pub luminance: Mat,
+    peak: f64,
+    channels: VectorOfMat,
+}
+
+impl Luminance {
+    pub fn split(image: &Mat, space: LuminanceSpace, peak: f64) -> Result<Luminance> {
+        let (forward, _) = space.codes();
+        let converted = {
+            let mut normalized = image.clone();
+            image.convert_to(&mut normalized, CV_32F, 1.0 / peak, 0.0)?;
+            let mut clone = normalized.clone();
+            cvt_color(&normalized, &mut clone, forward, 0)?;
+            clone
+        };
+
+        let mut channels = VectorOfMat::new();
+        split(&converted, &mut channels)?;
+
+        let channel = channels.get(space.luminance_channel())?;
+        let mut luminance = channel.clone();
+        channel.convert_to(&mut luminance, CV_32F, peak / space.luminance_range(), 0.0)?;
+
+        Ok(Luminance {
+            space,
+            luminance,
+            peak,
+            channels,
+        })
+    }
+
+    // Puts a processed luminance back in place of the original one, clipping
+    // it to the valid range so that the chroma is not distorted on the way back.
+    pub fn merge(&self, luminance: &Mat) -> Result<Mat> {
+        let (_, backward) = self.space.codes();
+
+        let channel = {
+            let mut clone = luminance.clone();
+            luminance.convert_to(&mut clone, CV_32F, 1.0, 0.0)?;
+            let mut truncated = clone.clone();
+            threshold(&clone, &mut truncated, self.peak, self.peak, THRESH_TRUNC)?;
+            let mut clamped = truncated.clone();
+            threshold(&truncated, &mut clamped, 0.0, 0.0, THRESH_TOZERO)?;
+            let mut scaled = clamped.clone();
+            clamped.convert_to(
+                &mut scaled,
+                CV_32F,
+                self.space.luminance_range() / self.peak,
+                0.0,
+            )?;
+            scaled
+        };
+
+        let mut channels = VectorOfMat::new();
+        for (i, original) in self.channels.iter().enumerate() {
+            if i == self.space.luminance_channel() {
+                channels.push(channel.clone());
+            } else {
+                channels.push(original);
+            }
+        }
+
+        let mut merged = channel.clone();
+        merge(&channels, &mut merged)?;
+
+        let mut converted = merged.clone();
+        cvt_color(&merged, &mut converted, backward, 0)?;
+
+        let mut clone = converted.clone();
+        converted.convert_to(&mut clone, CV_32F, self.peak, 0.0)?;
+        Ok(clone)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Three differently ordered ramps over [0, `peak`], one per channel.
+    fn bgr(rows: i32, cols: i32, peak: f64) -> Result<Mat> {
+        let mut channels = VectorOfMat::new();
+        for c in 0..3 {
+            let mut channel = Mat::zeros(rows, cols, CV_32F)?.to_mat()?;
+            for i in 0..rows {
+                for j in 0..cols {
+                    let step = (i * cols + j) * (2 * c + 1) % 17;
+                    *channel.at_2d_mut::<f32>(i, j)? = (step as f64 / 16.0 * peak) as f32;
+                }
+            }
+            channels.push(channel);
+        }
+        let mut image = channels.get(0)?.clone();
+        merge(&channels, &mut image)?;
+        Ok(image)
+    }
+
+    fn values(image: &Mat) -> Result<Vec<f32>> {
+        Ok(image.reshape(1, 0)?.data_typed::<f32>()?.to_vec())
+    }
+
+    #[test]
+    fn luminance_round_trips_through_every_space() -> Result<()> {
+        let peak = 255.0;
+        let image = bgr(8, 8, peak)?;
+        for name in LuminanceSpace::NAMES {
+            let luminance = Luminance::split(&image, LuminanceSpace::by_name(name).unwrap(), peak)?;
+            for &value in &values(&luminance.luminance)? {
+                assert!(
+                    value > -0.01 && value < peak as f32 + 0.01,
+                    "{}: {}",
+                    name,
+                    value
+                );
+            }
+            let merged = luminance.merge(&luminance.luminance)?;
+            for (a, b) in values(&image)?.iter().zip(values(&merged)?) {
+                assert!(
+                    ((a - b).abs() as f64) < 1e-3 * peak,
+                    "{}: {} {}",
+                    name,
+                    a,
+                    b
+                );
+            }
+        }
+        Ok(())
+    }
+}
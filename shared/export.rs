@@ -0,0 +1,183 @@
+use std::{fmt::Display, fs::File};
+
+use gif::{Encoder, Frame, Repeat};
+use opencv::{
+    core::{self, Mat, Point, Rect, Scalar, Size, CV_8U, CV_8UC3},
+    imgcodecs,
+    imgproc::{
+        cvt_color, put_text, resize, COLOR_BGR2RGB, COLOR_GRAY2BGR, FONT_HERSHEY_SIMPLEX,
+        INTER_AREA, LINE_AA,
+    },
+    prelude::*,
+    types::{VectorOfMat, VectorOfi32},
+    Error, Result,
+};
+
+use crate::{
+    channels,
+    normalization::{normalize, Normalization},
+};
+
+const LABEL_HEIGHT: i32 = 24;
+
+// Every stage is rendered the way `show` displays it: values in [0, `peak`],
+// scaled to fit into a `cell` and labelled with its name underneath.
+pub fn contact_sheet(
+    stages: &[(String, Mat)],
+    columns: usize,
+    cell: Size,
+    peak: f64,
+) -> Result<Mat> {
+    let columns = columns.max(1);
+    let rows = (stages.len() + columns - 1) / columns;
+    let frame_height = cell.height + LABEL_HEIGHT;
+
+    let sheet = Mat::new_rows_cols_with_default(
+        rows as i32 * frame_height,
+        columns as i32 * cell.width,
+        CV_8UC3,
+        Scalar::all(0.0),
+    )?;
+    for (i, (name, image)) in stages.iter().enumerate() {
+        let (row, column) = ((i / columns) as i32, (i % columns) as i32);
+        let mut target = Mat::roi(
+            &sheet,
+            Rect::new(
+                column * cell.width,
+                row * frame_height,
+                cell.width,
+                frame_height,
+            ),
+        )?;
+        labelled_frame(name, image, cell, peak)?.copy_to(&mut target)?;
+    }
+    Ok(sheet)
+}
+
+pub fn write_contact_sheet(
+    path: &str,
+    stages: &[(String, Mat)],
+    columns: usize,
+    peak: f64,
+) -> Result<()> {
+    let sheet = contact_sheet(stages, columns, Size::new(320, 320), peak)?;
+    write(path, &sheet)
+}
+
+// One page per stage, at the resolution of the stage itself. `imwrite` takes
+// a vector of pages on every OpenCV the labs pin, unlike `imwritemulti`.
+pub fn write_multipage_tiff(path: &str, stages: &[(String, Mat)], peak: f64) -> Result<()> {
+    let mut pages = VectorOfMat::new();
+    for (_, image) in stages {
+        pages.push(display_bgr(image, peak)?);
+    }
+    if !imgcodecs::imwrite(path, &pages, &VectorOfi32::new())? {
+        return Err(export_error(path, "could not write pages"));
+    }
+    Ok(())
+}
+
+// Loops forever through the labelled stages, `delay` is in 1/100 s.
+pub fn write_gif(
+    path: &str,
+    stages: &[(String, Mat)],
+    cell: Size,
+    delay: u16,
+    peak: f64,
+) -> Result<()> {
+    let file = File::create(path).map_err(|err| export_error(path, err))?;
+    let (width, height) = (cell.width as u16, (cell.height + LABEL_HEIGHT) as u16);
+    let mut encoder =
+        Encoder::new(file, width, height, &[]).map_err(|err| export_error(path, err))?;
+    encoder
+        .set_repeat(Repeat::Infinite)
+        .map_err(|err| export_error(path, err))?;
+
+    for (name, image) in stages {
+        let bgr = labelled_frame(name, image, cell, peak)?;
+        let mut rgb = bgr.clone();
+        cvt_color(&bgr, &mut rgb, COLOR_BGR2RGB, 0)?;
+        let pixels = rgb.reshape(1, 0)?;
+
+        let mut frame = Frame::from_rgb_speed(width, height, pixels.data_typed::<u8>()?, 10);
+        frame.delay = delay;
+        encoder
+            .write_frame(&frame)
+            .map_err(|err| export_error(path, err))?;
+    }
+    Ok(())
+}
+
+fn labelled_frame(name: &str, image: &Mat, cell: Size, peak: f64) -> Result<Mat> {
+    let mut frame = Mat::new_rows_cols_with_default(
+        cell.height + LABEL_HEIGHT,
+        cell.width,
+        CV_8UC3,
+        Scalar::all(0.0),
+    )?;
+
+    let image = display_bgr(image, peak)?;
+    let scale =
+        (cell.width as f64 / image.cols() as f64).min(cell.height as f64 / image.rows() as f64);
+    let size = Size::new(
+        ((image.cols() as f64 * scale) as i32).max(1),
+        ((image.rows() as f64 * scale) as i32).max(1),
+    );
+    let mut resized = image.clone();
+    resize(&image, &mut resized, size, 0.0, 0.0, INTER_AREA)?;
+
+    let mut target = Mat::roi(
+        &frame,
+        Rect::new(
+            (cell.width - size.width) / 2,
+            (cell.height - size.height) / 2,
+            size.width,
+            size.height,
+        ),
+    )?;
+    resized.copy_to(&mut target)?;
+
+    put_text(
+        &mut frame,
+        name,
+        Point::new(4, cell.height + LABEL_HEIGHT - 8),
+        FONT_HERSHEY_SIMPLEX,
+        0.5,
+        Scalar::all(255.0),
+        1,
+        LINE_AA,
+        false,
+    )?;
+    Ok(frame)
+}
+
+fn display_bgr(image: &Mat, peak: f64) -> Result<Mat> {
+    let (image, _) = normalize(
+        image,
+        Normalization::Fixed {
+            min: 0.0,
+            max: peak,
+        },
+        255.0,
+    )?;
+    let mut image_8u = image.clone();
+    image.convert_to(&mut image_8u, CV_8U, 1.0, 0.0)?;
+
+    if channels(&image_8u)? == 3 {
+        return Ok(image_8u);
+    }
+    let mut clone = image_8u.clone();
+    cvt_color(&image_8u, &mut clone, COLOR_GRAY2BGR, 0)?;
+    Ok(clone)
+}
+
+fn write(path: &str, image: &Mat) -> Result<()> {
+    if !imgcodecs::imwrite(path, image, &VectorOfi32::new())? {
+        return Err(export_error(path, "could not write image"));
+    }
+    Ok(())
+}
+
+fn export_error(path: &str, err: impl Display) -> Error {
+    Error::new(core::StsError, format!("{}: {}", path, err))
+}
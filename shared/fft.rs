@@ -0,0 +1,106 @@
+use opencv::{
+    core::{
+        copy_make_border, dft, get_optimal_dft_size, idft, merge, split, Mat, Rect, Scalar, Size,
+        BORDER_CONSTANT, CV_32F, DFT_COMPLEX_INPUT, DFT_COMPLEX_OUTPUT, DFT_REAL_OUTPUT, DFT_SCALE,
+    },
+    prelude::*,
+    types::VectorOfMat,
+    Result,
+};
+
+// A spectrum is a pair of CV_32F planes (re, im), shifted so that the zero
+// frequency is at (cols / 2, rows / 2).
+
+// The image is zero-padded on the right and bottom up to the next size the
+// DFT handles efficiently, so the spectrum may be larger than the image.
+pub fn fft_complex(image: &Mat) -> Result<(Mat, Mat)> {
+    let image = {
+        let mut padded = image.clone();
+        copy_make_border(
+            image,
+            &mut padded,
+            0,
+            get_optimal_dft_size(image.rows())? - image.rows(),
+            0,
+            get_optimal_dft_size(image.cols())? - image.cols(),
+            BORDER_CONSTANT,
+            Scalar::all(0.0),
+        )?;
+        padded
+    };
+    fft_complex_unpadded(&image)
+}
+
+// The spectrum of `image` at exactly its own size, for images whose opposite
+// edges are meant to meet, which padding would break; slower for sizes with
+// large prime factors.
+pub fn fft_complex_unpadded(image: &Mat) -> Result<(Mat, Mat)> {
+    let vec_of_mat = VectorOfMat::from(vec![
+        image.clone(),
+        Mat::zeros(image.rows(), image.cols(), CV_32F)?.to_mat()?,
+    ]);
+    let mut image_complex = image.clone();
+    merge(&vec_of_mat, &mut image_complex)?;
+
+    let mut image_dft = image.clone();
+    dft(
+        &image_complex,
+        &mut image_dft,
+        DFT_COMPLEX_OUTPUT | DFT_COMPLEX_INPUT | DFT_SCALE,
+        0,
+    )?;
+
+    let mut vec_of_mat = VectorOfMat::new();
+    split(&image_dft, &mut vec_of_mat)?;
+
+    Ok((
+        fft_shift(&vec_of_mat.get(0)?)?,
+        fft_shift(&vec_of_mat.get(1)?)?,
+    ))
+}
+
+// `size` is the size of the image before `fft_complex` padded it.
+pub fn ifft_complex(fft: &(Mat, Mat), size: Size) -> Result<Mat> {
+    let vec_of_mat = VectorOfMat::from(vec![ifft_shift(&fft.0)?, ifft_shift(&fft.1)?]);
+    let mut image_complex = fft.0.clone();
+    merge(&vec_of_mat, &mut image_complex)?;
+
+    let mut result = fft.0.clone();
+    idft(&image_complex, &mut result, DFT_REAL_OUTPUT, 0)?;
+    Ok(Mat::roi(&result, Rect::new(0, 0, size.width, size.height))?.clone())
+}
+
+// Moves the zero frequency to (cols / 2, rows / 2); for odd sizes this is not
+// its own inverse, see `ifft_shift`.
+pub fn fft_shift(image: &Mat) -> Result<Mat> {
+    roll(image, image.cols() / 2, image.rows() / 2)
+}
+
+pub fn ifft_shift(image: &Mat) -> Result<Mat> {
+    roll(
+        image,
+        image.cols() - image.cols() / 2,
+        image.rows() - image.rows() / 2,
+    )
+}
+
+// Cyclic shift: the pixel at (x, y) ends up at ((x + dx) % cols, (y + dy) % rows).
+pub fn roll(image: &Mat, dx: i32, dy: i32) -> Result<Mat> {
+    let (cols, rows) = (image.cols(), image.rows());
+    let (dx, dy) = (dx.rem_euclid(cols.max(1)), dy.rem_euclid(rows.max(1)));
+    let clone = image.clone();
+
+    let spans = |shift: i32, len: i32| [(0, shift, len - shift), (len - shift, 0, shift)];
+    for &(src_x, dst_x, width) in &spans(dx, cols) {
+        for &(src_y, dst_y, height) in &spans(dy, rows) {
+            if width == 0 || height == 0 {
+                continue;
+            }
+            let src = Mat::roi(image, Rect::new(src_x, src_y, width, height))?;
+            let mut dst = Mat::roi(&clone, Rect::new(dst_x, dst_y, width, height))?;
+            src.copy_to(&mut dst)?;
+        }
+    }
+
+    Ok(clone)
+}
@@ -0,0 +1,177 @@
+use std::fmt::Debug;
+
+use opencv::{
+    core::{Mat, Size, CV_32F},
+    prelude::*,
+    Result,
+};
+
+use crate::mul_mat_image;
+
+// A transfer function H(u, v) of the offsets from the zero frequency, in
+// pixels of a spectrum shifted by `fft_shift`: u along the columns, v along
+// the rows. Filters only become images in `rasterize`, so they can be
+// combined before knowing the spectrum they are applied to. The `Debug` form
+// lists every parameter, so it identifies the transfer function.
+pub trait FrequencyFilter: Debug {
+    fn response(&self, u: f64, v: f64) -> f64;
+
+    fn rasterize(&self, size: Size) -> Result<Mat> {
+        let mut filter = Mat::zeros(size.height, size.width, CV_32F)?.to_mat()?;
+        let (center_row, center_col) = (size.height / 2, size.width / 2);
+        for i in 0..size.height {
+            for j in 0..size.width {
+                *filter.at_2d_mut::<f32>(i, j)? =
+                    self.response((j - center_col) as f64, (i - center_row) as f64) as f32;
+            }
+        }
+        Ok(filter)
+    }
+
+    // 1 - H, turning a low-pass into a high-pass or a band-pass into a reject.
+    fn complement(self) -> Complement<Self>
+    where
+        Self: Sized,
+    {
+        Complement(self)
+    }
+
+    // mul·H + add, e.g. the gains of a high-frequency emphasis filter.
+    fn affine(self, mul: f64, add: f64) -> Affine<Self>
+    where
+        Self: Sized,
+    {
+        Affine {
+            filter: self,
+            mul,
+            add,
+        }
+    }
+}
+
+impl<F: FrequencyFilter + ?Sized> FrequencyFilter for &F {
+    fn response(&self, u: f64, v: f64) -> f64 {
+        (**self).response(u, v)
+    }
+}
+
+impl<F: FrequencyFilter + ?Sized> FrequencyFilter for Box<F> {
+    fn response(&self, u: f64, v: f64) -> f64 {
+        (**self).response(u, v)
+    }
+}
+
+pub fn apply_filter<F: FrequencyFilter + ?Sized>(
+    fft: &(Mat, Mat),
+    filter: &F,
+) -> Result<(Mat, Mat)> {
+    let filter = filter.rasterize(fft.0.size()?)?;
+    Ok((
+        mul_mat_image(&fft.0, &filter)?,
+        mul_mat_image(&fft.1, &filter)?,
+    ))
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Butterworth {
+    pub radius: f64,
+    pub n: i32,
+}
+
+impl FrequencyFilter for Butterworth {
+    fn response(&self, u: f64, v: f64) -> f64 {
+        1.0 / (1.0 + (u.hypot(v) / self.radius).powi(2 * self.n))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Gaussian {
+    pub radius: f64,
+}
+
+impl FrequencyFilter for Gaussian {
+    fn response(&self, u: f64, v: f64) -> f64 {
+        (-(u * u + v * v) / (2.0 * self.radius.powi(2))).exp()
+    }
+}
+
+// The low-pass whose complement a high-frequency emphasis filter boosts, in
+// sharpening as well as in homomorphic filtering.
+#[derive(Debug, Clone, Copy)]
+pub enum HighEmphasis {
+    Gaussian { radius: f64 },
+    Butterworth { radius: f64, n: i32 },
+}
+
+impl HighEmphasis {
+    pub const NAMES: &'static [&'static str] = &["gaussian", "butterworth"];
+
+    pub fn by_name(name: &str) -> Option<HighEmphasis> {
+        match name {
+            "gaussian" => Some(HighEmphasis::Gaussian { radius: 30.0 }),
+            "butterworth" => Some(HighEmphasis::Butterworth { radius: 30.0, n: 2 }),
+            _ => None,
+        }
+    }
+
+    pub fn low_pass(self) -> Box<dyn FrequencyFilter> {
+        match self {
+            HighEmphasis::Gaussian { radius } => Box::new(Gaussian { radius }),
+            HighEmphasis::Butterworth { radius, n } => Box::new(Butterworth { radius, n }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Complement<F>(pub F);
+
+impl<F: FrequencyFilter> FrequencyFilter for Complement<F> {
+    fn response(&self, u: f64, v: f64) -> f64 {
+        1.0 - self.0.response(u, v)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Affine<F> {
+    pub filter: F,
+    pub mul: f64,
+    pub add: f64,
+}
+
+impl<F: FrequencyFilter> FrequencyFilter for Affine<F> {
+    fn response(&self, u: f64, v: f64) -> f64 {
+        self.mul * self.filter.response(u, v) + self.add
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn emphasis_rises_from_low_to_low_plus_high() {
+        let (low, high) = (0.5, 1.5);
+        for name in HighEmphasis::NAMES {
+            let low_pass = HighEmphasis::by_name(name).unwrap().low_pass();
+            let emphasis = (&low_pass).complement().affine(high, low);
+            assert!(close(emphasis.response(0.0, 0.0), low), "{}", name);
+            assert!(close(emphasis.response(1e4, 0.0), low + high), "{}", name);
+            for &(u, v) in &[(18.0, 24.0), (0.0, -30.0), (-45.0, 60.0)] {
+                let expected = low + high * (1.0 - low_pass.response(u, v));
+                assert!(close(emphasis.response(u, v), expected), "{}", name);
+            }
+        }
+        assert!(close(
+            Butterworth { radius: 10.0, n: 2 }.response(6.0, 8.0),
+            0.5
+        ));
+        assert!(close(
+            Gaussian { radius: 10.0 }.response(0.0, 10.0),
+            (-0.5f64).exp()
+        ));
+    }
+}
@@ -0,0 +1,50 @@
+use opencv::{
+    core::{exp, log, Mat, CV_32F},
+    prelude::*,
+    Result,
+};
+
+use crate::{
+    fft::{fft_complex, ifft_complex},
+    filters::{apply_filter, FrequencyFilter, HighEmphasis},
+    normalization::{normalize, Normalization},
+};
+
+// Illumination varies slowly and multiplies the reflectance, so in the log
+// domain it becomes an additive low-frequency term. `low` < 1 suppresses it,
+// `high` > 1 boosts the reflectance detail.
+#[derive(Debug, Clone, Copy)]
+pub struct Homomorphic {
+    pub filter: HighEmphasis,
+    pub low: f64,
+    pub high: f64,
+}
+
+// Works on CV_32F images in [0, 1]; the result is stretched back to [0, 1]
+// so that it can be fed to a fixed threshold.
+pub fn homomorphic(image: &Mat, params: &Homomorphic) -> Result<Mat> {
+    let image_log = {
+        let mut shifted = image.clone();
+        image.convert_to(&mut shifted, CV_32F, 1.0, 1.0)?;
+        let mut clone = shifted.clone();
+        log(&shifted, &mut clone)?;
+        clone
+    };
+    let fft = fft_complex(&image_log)?;
+
+    let filter = params
+        .filter
+        .low_pass()
+        .complement()
+        .affine(params.high - params.low, params.low);
+    let image_filtered = ifft_complex(&apply_filter(&fft, &filter)?, image.size()?)?;
+
+    let image_exp = {
+        let mut clone = image_filtered.clone();
+        exp(&image_filtered, &mut clone)?;
+        let mut shifted = clone.clone();
+        clone.convert_to(&mut shifted, CV_32F, 1.0, -1.0)?;
+        shifted
+    };
+    Ok(normalize(&image_exp, Normalization::MinMax, 1.0)?.0)
+}
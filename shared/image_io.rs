@@ -0,0 +1,152 @@
+use std::path::Path;
+
+use opencv::{
+    core::{self, Mat, CV_16S, CV_16U, CV_32F, CV_32S, CV_64F, CV_8S, CV_8U},
+    imgcodecs::{self, IMREAD_ANYDEPTH},
+    prelude::*,
+    types::VectorOfi32,
+    Error, Result,
+};
+
+use crate::depth;
+
+// Loads a single-channel image keeping its bit depth (8/16-bit PNG and TIFF,
+// 32-bit float TIFF, PFM), then converts it to CV_32F so that the full range
+// of the source depth maps to [0, `peak`]. Float sources are expected in [0, 1].
+pub fn load_float(path: &str, peak: f64) -> Result<Mat> {
+    load_float_with(path, IMREAD_ANYDEPTH, peak)
+}
+
+// Same as `load_float` with other `imread` flags, e.g. to keep colour.
+pub fn load_float_with(path: &str, flags: i32, peak: f64) -> Result<Mat> {
+    let image = load(path, flags)?;
+    let mut clone = image.clone();
+    image.convert_to(&mut clone, CV_32F, peak / full_scale(depth(&image)?)?, 0.0)?;
+    Ok(clone)
+}
+
+pub fn load(path: &str, flags: i32) -> Result<Mat> {
+    let image = imgcodecs::imread(path, flags)?;
+    if image.rows() == 0 || image.cols() == 0 {
+        return Err(Error::new(
+            core::StsError,
+            format!("could not read image {}", path),
+        ));
+    }
+    Ok(image)
+}
+
+// Writes float data without quantisation; only formats with a float codec
+// (TIFF, PFM, EXR) are accepted.
+pub fn save_float(path: &str, image: &Mat) -> Result<()> {
+    match extension(path).as_deref() {
+        Some("tif") | Some("tiff") | Some("pfm") | Some("exr") => {}
+        _ => {
+            return Err(Error::new(
+                core::StsBadArg,
+                format!("{}: float images need a .tiff, .pfm or .exr file", path),
+            ))
+        }
+    }
+    let mut clone = image.clone();
+    image.convert_to(&mut clone, CV_32F, 1.0, 0.0)?;
+    save(path, &clone)
+}
+
+// Maps [0, `peak`] onto the full 16-bit range, saturating outside of it.
+pub fn save_16u(path: &str, image: &Mat, peak: f64) -> Result<()> {
+    match extension(path).as_deref() {
+        Some("png") | Some("tif") | Some("tiff") => {}
+        _ => {
+            return Err(Error::new(
+                core::StsBadArg,
+                format!("{}: 16-bit images need a .png or .tiff file", path),
+            ))
+        }
+    }
+    let mut clone = image.clone();
+    image.convert_to(&mut clone, CV_16U, full_scale(CV_16U)? / peak, 0.0)?;
+    save(path, &clone)
+}
+
+fn save(path: &str, image: &Mat) -> Result<()> {
+    if !imgcodecs::imwrite(path, image, &VectorOfi32::new())? {
+        return Err(Error::new(
+            core::StsError,
+            format!("could not write image {}", path),
+        ));
+    }
+    Ok(())
+}
+
+// The value that stands for `peak`: the largest one of an integer depth, 1
+// for floats. Signed depths keep their negative values below 0.
+fn full_scale(depth: i32) -> Result<f64> {
+    match depth {
+        CV_8U => Ok(u8::MAX as f64),
+        CV_8S => Ok(i8::MAX as f64),
+        CV_16U => Ok(u16::MAX as f64),
+        CV_16S => Ok(i16::MAX as f64),
+        CV_32S => Ok(i32::MAX as f64),
+        CV_32F | CV_64F => Ok(1.0),
+        _ => Err(Error::new(
+            core::StsUnsupportedFormat,
+            format!("images of depth {} are not supported", depth),
+        )),
+    }
+}
+
+fn extension(path: &str) -> Option<String> {
+    Path::new(path)
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sixteen_bit_files_round_trip() -> Result<()> {
+        let peak = 255.0;
+        let mut image = Mat::zeros(4, 64, CV_32F)?.to_mat()?;
+        for i in 0..4 {
+            for j in 0..64 {
+                *image.at_2d_mut::<f32>(i, j)? = (j as f64 / 63.0 * peak - i as f64 * 0.01) as f32;
+            }
+        }
+        let path = std::env::temp_dir().join(format!("image-io-{}.png", std::process::id()));
+        let path = path.to_str().unwrap();
+        save_16u(path, &image, peak)?;
+        let loaded = load_float(path, peak)?;
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(depth(&loaded)?, CV_32F);
+        // Only the 16-bit quantisation is lost.
+        let step = (peak / 65535.0) as f32;
+        for (original, loaded) in image
+            .data_typed::<f32>()?
+            .iter()
+            .zip(loaded.data_typed::<f32>()?)
+        {
+            assert!(
+                (original.max(0.0) - loaded).abs() <= step,
+                "{} {}",
+                original,
+                loaded
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn every_integer_depth_has_a_full_scale() -> Result<()> {
+        assert_eq!(full_scale(CV_8U)?, 255.0);
+        assert_eq!(full_scale(CV_16S)?, 32767.0);
+        assert_eq!(full_scale(CV_32S)?, 2147483647.0);
+        assert_eq!(full_scale(CV_64F)?, 1.0);
+        // 7 is CV_16F, which only the newer OpenCV versions know.
+        assert!(full_scale(7).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,623 @@
+use std::fs;
+
+use opencv::{
+    core::{
+        self, copy_make_border, dft, get_optimal_dft_size, idft, mul_spectrums, Mat, Point, Rect,
+        Scalar, BORDER_CONSTANT, BORDER_REFLECT, BORDER_REFLECT_101, BORDER_REPLICATE, BORDER_WRAP,
+        CV_32F, CV_64FC1, DFT_REAL_OUTPUT, DFT_SCALE,
+    },
+    imgproc::{filter_2d, sep_filter_2d},
+    prelude::*,
+    Error, Result,
+};
+
+#[derive(Debug, Clone, Copy)]
+pub enum Border {
+    Constant,
+    Replicate,
+    Reflect,
+    Reflect101,
+    Wrap,
+}
+
+impl Border {
+    pub const NAMES: &'static [&'static str] =
+        &["constant", "replicate", "reflect", "reflect101", "wrap"];
+
+    pub fn by_name(name: &str) -> Option<Border> {
+        match name {
+            "constant" => Some(Border::Constant),
+            "replicate" => Some(Border::Replicate),
+            "reflect" => Some(Border::Reflect),
+            "reflect101" => Some(Border::Reflect101),
+            "wrap" => Some(Border::Wrap),
+            _ => None,
+        }
+    }
+
+    fn code(self) -> i32 {
+        match self {
+            Border::Constant => BORDER_CONSTANT,
+            Border::Replicate => BORDER_REPLICATE,
+            Border::Reflect => BORDER_REFLECT,
+            Border::Reflect101 => BORDER_REFLECT_101,
+            Border::Wrap => BORDER_WRAP,
+        }
+    }
+}
+
+// How a kernel is applied; all methods give the same result up to rounding.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Method {
+    Direct,
+    // Only for kernels that `separable` can split.
+    Separable,
+    // Through the DFT, faster for large kernels.
+    Fourier,
+}
+
+// Row-major kernel with its anchor in the centre (rounded down for even
+// sizes). Weights are always finite, a NaN would poison every filtered pixel.
+#[derive(Debug, Clone)]
+pub struct Kernel {
+    rows: usize,
+    cols: usize,
+    data: Vec<f64>,
+}
+
+impl Kernel {
+    pub fn new(rows: usize, cols: usize, data: Vec<f64>) -> Result<Kernel> {
+        if rows == 0 || cols == 0 || data.len() != rows * cols {
+            return Err(Error::new(
+                core::StsBadArg,
+                format!("kernel of {}x{} needs {} values", rows, cols, rows * cols),
+            ));
+        }
+        if let Some(value) = data.iter().find(|value| !value.is_finite()) {
+            return Err(Error::new(
+                core::StsBadArg,
+                format!("kernel weight {} is not finite", value),
+            ));
+        }
+        Ok(Kernel { rows, cols, data })
+    }
+
+    // Any single-channel matrix, such as the kernels `imgproc` builds.
+    pub fn from_mat(mat: &Mat) -> Result<Kernel> {
+        let mut mat_64f = mat.clone();
+        mat.convert_to(&mut mat_64f, CV_64FC1, 1.0, 0.0)?;
+        let mut data = vec![];
+        for i in 0..mat.rows() {
+            for j in 0..mat.cols() {
+                data.push(*mat_64f.at_2d::<f64>(i, j)?);
+            }
+        }
+        Kernel::new(mat.rows() as usize, mat.cols() as usize, data)
+    }
+
+    pub fn laplacian() -> Kernel {
+        Kernel {
+            rows: 3,
+            cols: 3,
+            data: vec![1.0, 1.0, 1.0, 1.0, -8.0, 1.0, 1.0, 1.0, 1.0],
+        }
+    }
+
+    pub fn load(path: &str) -> Result<Kernel> {
+        let text = fs::read_to_string(path)
+            .map_err(|err| Error::new(core::StsError, format!("{}: {}", path, err)))?;
+        Kernel::parse(&text)
+    }
+
+    // One matrix row per line, values separated by spaces or commas. Lines
+    // starting with `#` are comments, and two optional settings may precede
+    // the matrix:
+    //
+    //     scale = 1/16
+    //     normalize = true
+    pub fn parse(text: &str) -> Result<Kernel> {
+        let mut scale = 1.0;
+        let mut normalize = false;
+        let mut rows = vec![];
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some((key, value)) = split_setting(line) {
+                if !rows.is_empty() {
+                    return Err(parse_error(number, "settings must precede the matrix"));
+                }
+                match key {
+                    "scale" => scale = parse_number(value, number)?,
+                    "normalize" => normalize = parse_bool(value, number)?,
+                    _ => return Err(parse_error(number, &format!("unknown setting {}", key))),
+                }
+                continue;
+            }
+
+            let row = line
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|value| !value.is_empty())
+                .map(|value| parse_number(value, number))
+                .collect::<Result<Vec<f64>>>()?;
+            if let Some(first) = rows.first() {
+                if row.len() != first.len() {
+                    return Err(parse_error(number, "rows have different lengths"));
+                }
+            }
+            rows.push(row);
+        }
+
+        let (height, width) = (rows.len(), rows.first().map_or(0, Vec::len));
+        let kernel = Kernel::new(height, width, rows.concat())?.scaled(scale);
+        Ok(if normalize {
+            kernel.normalized()
+        } else {
+            kernel
+        })
+    }
+
+    // (rows, cols)
+    pub fn size(&self) -> (usize, usize) {
+        (self.rows, self.cols)
+    }
+
+    pub fn scaled(&self, scale: f64) -> Kernel {
+        Kernel {
+            rows: self.rows,
+            cols: self.cols,
+            data: self.data.iter().map(|value| value * scale).collect(),
+        }
+    }
+
+    // Scales the kernel to a unit sum; zero-sum kernels (edge detectors,
+    // Laplacians) are scaled to a unit sum of absolute values instead.
+    pub fn normalized(&self) -> Kernel {
+        let sum: f64 = self.data.iter().sum();
+        let abs_sum: f64 = self.data.iter().map(|value| value.abs()).sum();
+        if sum.abs() > 1e-9 {
+            self.scaled(1.0 / sum)
+        } else if abs_sum > 1e-9 {
+            self.scaled(1.0 / abs_sum)
+        } else {
+            self.clone()
+        }
+    }
+
+    // Rotated by 180°, which turns a correlation into a convolution.
+    pub fn flipped(&self) -> Kernel {
+        Kernel {
+            rows: self.rows,
+            cols: self.cols,
+            data: self.data.iter().rev().copied().collect(),
+        }
+    }
+
+    // Splits a rank-1 kernel into a column and a row vector whose outer
+    // product gives back the kernel.
+    pub fn separable(&self) -> Option<(Vec<f64>, Vec<f64>)> {
+        let (pivot, &pivot_value) = self
+            .data
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.abs().partial_cmp(&b.1.abs()).unwrap())?;
+        if pivot_value.abs() < 1e-12 {
+            return None;
+        }
+        let (pivot_row, pivot_col) = (pivot / self.cols, pivot % self.cols);
+
+        let column: Vec<f64> = (0..self.rows).map(|i| self.at(i, pivot_col)).collect();
+        let row: Vec<f64> = (0..self.cols)
+            .map(|j| self.at(pivot_row, j) / pivot_value)
+            .collect();
+
+        let tolerance = 1e-9 * pivot_value.abs().max(1.0);
+        for i in 0..self.rows {
+            for j in 0..self.cols {
+                if (column[i] * row[j] - self.at(i, j)).abs() > tolerance {
+                    return None;
+                }
+            }
+        }
+        Some((column, row))
+    }
+
+    pub fn to_mat(&self) -> Result<Mat> {
+        let mut mat = Mat::zeros(self.rows as i32, self.cols as i32, CV_64FC1)?.to_mat()?;
+        for i in 0..self.rows {
+            for j in 0..self.cols {
+                *mat.at_2d_mut::<f64>(i as i32, j as i32)? = self.at(i, j);
+            }
+        }
+        Ok(mat)
+    }
+
+    // True convolution by the cheapest method for this image, see
+    // `method_for`.
+    pub fn convolve(&self, image: &Mat, border: Border) -> Result<Mat> {
+        self.convolve_by(image, border, self.method_for(image)?)
+    }
+
+    pub fn convolve_by(&self, image: &Mat, border: Border, method: Method) -> Result<Mat> {
+        self.flipped()
+            .correlate_anchored(image, border, self.convolution_anchor(), method)
+    }
+
+    pub fn correlate_by(&self, image: &Mat, border: Border, method: Method) -> Result<Mat> {
+        self.correlate_anchored(image, border, (self.rows / 2, self.cols / 2), method)
+    }
+
+    // Where the flipped kernel is anchored, so that convolving with a
+    // symmetric kernel gives the same result as correlating with it.
+    pub fn convolution_anchor(&self) -> (usize, usize) {
+        (self.rows - 1 - self.rows / 2, self.cols - 1 - self.cols / 2)
+    }
+
+    // Rank-1 kernels are always cheapest separably. Otherwise the direct cost
+    // grows with the kernel area, the FFT cost with log2 of the padded area.
+    pub fn method_for(&self, image: &Mat) -> Result<Method> {
+        if self.separable().is_some() {
+            return Ok(Method::Separable);
+        }
+        let (rows, cols) = self.size();
+        let (rows, cols) = (rows as i32, cols as i32);
+        let area = (rows * cols) as f64;
+        let padded = (get_optimal_dft_size(image.rows() + 2 * (rows - 1))?
+            * get_optimal_dft_size(image.cols() + 2 * (cols - 1))?) as f64;
+        if area > 4.0 * padded.log2() {
+            Ok(Method::Fourier)
+        } else {
+            Ok(Method::Direct)
+        }
+    }
+
+    // `filter_2d` does not support BORDER_WRAP, so the border is added
+    // explicitly and cut away again after filtering, the same way for every
+    // method.
+    fn correlate_anchored(
+        &self,
+        image: &Mat,
+        border: Border,
+        anchor: (usize, usize),
+        method: Method,
+    ) -> Result<Mat> {
+        let (anchor_row, anchor_col) = (anchor.0 as i32, anchor.1 as i32);
+        let mut padded = image.clone();
+        copy_make_border(
+            image,
+            &mut padded,
+            anchor_row,
+            self.rows as i32 - 1 - anchor_row,
+            anchor_col,
+            self.cols as i32 - 1 - anchor_col,
+            border.code(),
+            Scalar::all(0.0),
+        )?;
+
+        let anchor = Point::new(anchor_col, anchor_row);
+        let (filtered, offset) = match method {
+            Method::Direct => {
+                let mut filtered = padded.clone();
+                filter_2d(
+                    &padded,
+                    &mut filtered,
+                    CV_32F,
+                    &self.to_mat()?,
+                    anchor,
+                    0.0,
+                    BORDER_CONSTANT,
+                )?;
+                (filtered, anchor)
+            }
+            Method::Separable => {
+                let (column, row) = self.separable().ok_or_else(|| {
+                    Error::new(core::StsBadArg, "kernel is not separable".to_string())
+                })?;
+                let mut filtered = padded.clone();
+                sep_filter_2d(
+                    &padded,
+                    &mut filtered,
+                    CV_32F,
+                    &vector_mat(&row)?,
+                    &vector_mat(&column)?,
+                    anchor,
+                    0.0,
+                    BORDER_CONSTANT,
+                )?;
+                (filtered, anchor)
+            }
+            // The full correlation has output pixel (y, x) at
+            // (y + rows - 1, x + cols - 1) instead of at the anchor.
+            Method::Fourier => (
+                self.fourier_correlate(&padded)?,
+                Point::new(self.cols as i32 - 1, self.rows as i32 - 1),
+            ),
+        };
+
+        Ok(Mat::roi(
+            &filtered,
+            Rect::new(offset.x, offset.y, image.cols(), image.rows()),
+        )?
+        .clone())
+    }
+
+    // Full linear correlation of `image` with the kernel through packed real
+    // spectra, zero-padded to a DFT-friendly size at least as large as the
+    // result, so that nothing wraps around.
+    fn fourier_correlate(&self, image: &Mat) -> Result<Mat> {
+        let rows = get_optimal_dft_size(image.rows() + self.rows as i32 - 1)?;
+        let cols = get_optimal_dft_size(image.cols() + self.cols as i32 - 1)?;
+        let spectrum = |mat: &Mat| -> Result<Mat> {
+            let mut mat_32f = mat.clone();
+            mat.convert_to(&mut mat_32f, CV_32F, 1.0, 0.0)?;
+            let mut zero_padded = mat_32f.clone();
+            copy_make_border(
+                &mat_32f,
+                &mut zero_padded,
+                0,
+                rows - mat.rows(),
+                0,
+                cols - mat.cols(),
+                BORDER_CONSTANT,
+                Scalar::all(0.0),
+            )?;
+            let mut result = zero_padded.clone();
+            dft(&zero_padded, &mut result, 0, mat.rows())?;
+            Ok(result)
+        };
+
+        // Correlation is convolution with the flipped kernel.
+        let image_spectrum = spectrum(image)?;
+        let mut product = image_spectrum.clone();
+        mul_spectrums(
+            &image_spectrum,
+            &spectrum(&self.flipped().to_mat()?)?,
+            &mut product,
+            0,
+            false,
+        )?;
+        let mut result = product.clone();
+        idft(&product, &mut result, DFT_SCALE | DFT_REAL_OUTPUT, 0)?;
+        Ok(result)
+    }
+
+    fn at(&self, row: usize, col: usize) -> f64 {
+        self.data[row * self.cols + col]
+    }
+}
+
+fn vector_mat(values: &[f64]) -> Result<Mat> {
+    let mut mat = Mat::zeros(values.len() as i32, 1, CV_64FC1)?.to_mat()?;
+    for (i, value) in values.iter().enumerate() {
+        *mat.at_2d_mut::<f64>(i as i32, 0)? = *value;
+    }
+    Ok(mat)
+}
+
+fn split_setting(line: &str) -> Option<(&str, &str)> {
+    let mut parts = line.splitn(2, '=');
+    let key = parts.next()?.trim();
+    let value = parts.next()?.trim();
+    Some((key, value))
+}
+
+// Accepts plain numbers as well as fractions like `1/16`. `nan`, `inf` and
+// divisions by zero are rejected, they would poison every filtered pixel.
+fn parse_number(value: &str, line: usize) -> Result<f64> {
+    let parsed = match value.find('/') {
+        Some(slash) => value[..slash]
+            .trim()
+            .parse::<f64>()
+            .and_then(|num| Ok(num / value[slash + 1..].trim().parse::<f64>()?)),
+        None => value.parse::<f64>(),
+    };
+    match parsed {
+        Ok(number) if number.is_finite() => Ok(number),
+        Ok(_) => Err(parse_error(line, &format!("{} is not finite", value))),
+        Err(_) => Err(parse_error(line, &format!("{} is not a number", value))),
+    }
+}
+
+fn parse_bool(value: &str, line: usize) -> Result<bool> {
+    match value {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => Err(parse_error(
+            line,
+            &format!("{} is neither true nor false", value),
+        )),
+    }
+}
+
+fn parse_error(line: usize, message: &str) -> Error {
+    Error::new(
+        core::StsParseError,
+        format!("kernel line {}: {}", line + 1, message),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use opencv::core::border_interpolate;
+
+    use super::*;
+
+    const BORDERS: [Border; 5] = [
+        Border::Constant,
+        Border::Replicate,
+        Border::Reflect,
+        Border::Reflect101,
+        Border::Wrap,
+    ];
+
+    fn ramp(rows: i32, cols: i32) -> Result<Mat> {
+        let mut image = Mat::zeros(rows, cols, CV_32F)?.to_mat()?;
+        for i in 0..rows {
+            for j in 0..cols {
+                *image.at_2d_mut::<f32>(i, j)? = ((i * 31 + j * 17 + i * j) % 23) as f32 / 23.0;
+            }
+        }
+        Ok(image)
+    }
+
+    // out(y, x) = sum of k(a, b) · image(y - a + rows / 2, x - b + cols / 2),
+    // with the border resolved by OpenCV's own rule.
+    fn reference(kernel: &Kernel, image: &Mat, border: Border) -> Result<Vec<f64>> {
+        let (rows, cols) = (image.rows(), image.cols());
+        let mut result = vec![];
+        for y in 0..rows {
+            for x in 0..cols {
+                let mut sum = 0.0;
+                for a in 0..kernel.rows {
+                    for b in 0..kernel.cols {
+                        let i = border_interpolate(
+                            y - a as i32 + kernel.rows as i32 / 2,
+                            rows,
+                            border.code(),
+                        )?;
+                        let j = border_interpolate(
+                            x - b as i32 + kernel.cols as i32 / 2,
+                            cols,
+                            border.code(),
+                        )?;
+                        if i >= 0 && j >= 0 {
+                            sum += kernel.at(a, b) * *image.at_2d::<f32>(i, j)? as f64;
+                        }
+                    }
+                }
+                result.push(sum);
+            }
+        }
+        Ok(result)
+    }
+
+    #[test]
+    fn every_method_and_border_matches_the_reference() -> Result<()> {
+        let kernels = [
+            Kernel::laplacian(),
+            Kernel::parse("1 2 1\n2 4 2\n1 2 1\n")?.normalized(),
+            Kernel::new(2, 3, vec![1.0, -1.0, 3.0, 2.0, -2.0, 6.0])?,
+            Kernel::new(4, 5, (0..20).map(|i| ((i * 7) % 11) as f64 - 5.0).collect())?,
+        ];
+        let image = ramp(23, 30)?;
+        for kernel in &kernels {
+            let mut methods = vec![Method::Direct, Method::Fourier];
+            if kernel.separable().is_some() {
+                methods.push(Method::Separable);
+            }
+            for &border in &BORDERS {
+                let expected = reference(kernel, &image, border)?;
+                for &method in &methods {
+                    let result = kernel.convolve_by(&image, border, method)?;
+                    let values = result.data_typed::<f32>()?;
+                    let difference = expected
+                        .iter()
+                        .zip(values)
+                        .map(|(e, &v)| (e - v as f64).abs())
+                        .fold(0.0, f64::max);
+                    assert!(
+                        difference < 1e-3,
+                        "{:?} {:?} {:?}: {}",
+                        kernel,
+                        border,
+                        method,
+                        difference
+                    );
+                }
+            }
+        }
+        assert!(Kernel::laplacian()
+            .convolve_by(&image, Border::Reflect, Method::Separable)
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn parses_settings_fractions_and_comments() -> Result<()> {
+        let kernel = Kernel::parse("# box\nscale = 1/4\n1, 1\n1 1\n")?;
+        assert_eq!((kernel.rows, kernel.cols), (2, 2));
+        assert_eq!(kernel.data, vec![0.25; 4]);
+
+        let kernel = Kernel::parse("normalize = true\n1 2 1\n")?;
+        assert_eq!(kernel.data, vec![0.25, 0.5, 0.25]);
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_malformed_kernels() {
+        for text in &[
+            "",
+            "1 2\n3\n",
+            "1 x\n",
+            "nan 1\n",
+            "inf 1\n",
+            "1/0 1\n",
+            "normalize = yes\n1\n",
+            "size = 3\n1\n",
+            "1 2\nscale = 2\n",
+        ] {
+            assert!(Kernel::parse(text).is_err(), "{:?} was accepted", text);
+        }
+    }
+
+    #[test]
+    fn rejects_non_finite_weights() -> Result<()> {
+        assert!(Kernel::new(1, 2, vec![1.0, f64::NAN]).is_err());
+        let mut mat = Mat::zeros(2, 2, CV_32F)?.to_mat()?;
+        *mat.at_2d_mut::<f32>(1, 0)? = f32::INFINITY;
+        assert!(Kernel::from_mat(&mat).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn normalizes_zero_sum_kernels_by_absolute_sum() {
+        let kernel = Kernel::laplacian().normalized();
+        assert!((kernel.data.iter().map(|v| v.abs()).sum::<f64>() - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn splits_rank_one_kernels_only() -> Result<()> {
+        let gaussian = Kernel::parse("1 2 1\n2 4 2\n1 2 1\n")?;
+        let (column, row) = gaussian.separable().unwrap();
+        for i in 0..3 {
+            for j in 0..3 {
+                assert!((column[i] * row[j] - gaussian.at(i, j)).abs() < 1e-12);
+            }
+        }
+        assert!(Kernel::laplacian().separable().is_none());
+        assert!(Kernel::new(2, 2, vec![0.0; 4])?.separable().is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn picks_the_method_from_the_kernel_and_image_size() -> Result<()> {
+        let image = ramp(64, 64)?;
+        let disk = (0..31 * 31)
+            .map(|i| {
+                let (y, x) = (i / 31 - 15, i % 31 - 15);
+                if x * x + y * y <= 225 {
+                    1.0
+                } else {
+                    0.0
+                }
+            })
+            .collect();
+        let gaussian = Kernel::parse("1 2 1\n2 4 2\n1 2 1\n")?;
+        assert_eq!(gaussian.method_for(&image)?, Method::Separable);
+        assert_eq!(Kernel::laplacian().method_for(&image)?, Method::Direct);
+        assert_eq!(
+            Kernel::new(31, 31, disk)?.method_for(&image)?,
+            Method::Fourier
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn flipping_rotates_by_half_a_turn() -> Result<()> {
+        let kernel = Kernel::new(2, 3, vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0])?;
+        assert_eq!(kernel.flipped().data, vec![6.0, 5.0, 4.0, 3.0, 2.0, 1.0]);
+        Ok(())
+    }
+}
@@ -0,0 +1,259 @@
+use opencv::{
+    core::{
+        absdiff, add, divide2, mean, multiply, subtract, Mat, Size, BORDER_DEFAULT, CV_32F, CV_8U,
+    },
+    imgproc::{gaussian_blur, laplacian, pyr_down, sobel},
+    prelude::*,
+    Result,
+};
+
+use crate::channels;
+
+const MS_SSIM_WEIGHTS: [f64; 5] = [0.0448, 0.2856, 0.3001, 0.2363, 0.1333];
+
+// Full-reference metrics take `peak` as the largest possible pixel value,
+// e.g. 255.0 for 0..255 float images and 1.0 for 0..1 ones.
+pub fn mse(left: &Mat, right: &Mat) -> Result<f64> {
+    let diff = {
+        let mut clone = to_float(left)?;
+        absdiff(&to_float(left)?, &to_float(right)?, &mut clone)?;
+        clone
+    };
+    mean_value(&mul_images(&diff, &diff)?)
+}
+
+pub fn psnr(left: &Mat, right: &Mat, peak: f64) -> Result<f64> {
+    let mse = mse(left, right)?;
+    if mse == 0.0 {
+        return Ok(f64::INFINITY);
+    }
+    Ok(10.0 * (peak * peak / mse).log10())
+}
+
+pub fn ssim(left: &Mat, right: &Mat, peak: f64) -> Result<f64> {
+    mean_value(&ssim_map(left, right, peak)?)
+}
+
+pub fn ssim_map(left: &Mat, right: &Mat, peak: f64) -> Result<Mat> {
+    Ok(ssim_components(&to_float(left)?, &to_float(right)?, peak)?.0)
+}
+
+// Wang, Simoncelli, Bovik: "Multi-scale structural similarity for image
+// quality assessment". Images are evaluated on fewer scales once they get
+// smaller than 32 pixels; the weights are then renormalised over the scales
+// that were evaluated, otherwise the missing factors would bias the score
+// towards 1.
+pub fn ms_ssim(left: &Mat, right: &Mat, peak: f64) -> Result<f64> {
+    let mut left = to_float(left)?;
+    let mut right = to_float(right)?;
+    let mut values = vec![];
+    for (scale, weight) in MS_SSIM_WEIGHTS.iter().enumerate() {
+        let (ssim, cs) = ssim_components(&left, &right, peak)?;
+        let last = scale + 1 == MS_SSIM_WEIGHTS.len() || left.rows().min(left.cols()) < 32;
+        let value = if last {
+            mean_value(&ssim)?
+        } else {
+            mean_value(&cs)?
+        };
+        values.push((value.max(0.0), *weight));
+        if last {
+            break;
+        }
+        left = half_size(&left)?;
+        right = half_size(&right)?;
+    }
+    let total: f64 = values.iter().map(|(_, weight)| weight).sum();
+    Ok(values
+        .iter()
+        .map(|(value, weight)| value.powf(weight / total))
+        .product())
+}
+
+pub fn variance_of_laplacian(image: &Mat) -> Result<f64> {
+    let mut image_laplacian = to_float(image)?;
+    laplacian(
+        &to_float(image)?,
+        &mut image_laplacian,
+        CV_32F,
+        3,
+        1.0,
+        0.0,
+        BORDER_DEFAULT,
+    )?;
+    let average = mean_value(&image_laplacian)?;
+    Ok(mean_value(&mul_images(&image_laplacian, &image_laplacian)?)? - average * average)
+}
+
+pub fn tenengrad(image: &Mat) -> Result<f64> {
+    let image = to_float(image)?;
+    let mut gx = image.clone();
+    let mut gy = image.clone();
+    sobel(&image, &mut gx, CV_32F, 1, 0, 3, 1.0, 0.0, BORDER_DEFAULT)?;
+    sobel(&image, &mut gy, CV_32F, 0, 1, 3, 1.0, 0.0, BORDER_DEFAULT)?;
+    let mut sum = image.clone();
+    add(
+        &mul_images(&gx, &gx)?,
+        &mul_images(&gy, &gy)?,
+        &mut sum,
+        &no_mask()?,
+        CV_32F,
+    )?;
+    mean_value(&sum)
+}
+
+// Returns the SSIM map and the contrast-structure map used by MS-SSIM.
+fn ssim_components(left: &Mat, right: &Mat, peak: f64) -> Result<(Mat, Mat)> {
+    let c1 = (0.01 * peak).powi(2);
+    let c2 = (0.03 * peak).powi(2);
+
+    let mu_left = blurred(left)?;
+    let mu_right = blurred(right)?;
+    let mu_left_sq = mul_images(&mu_left, &mu_left)?;
+    let mu_right_sq = mul_images(&mu_right, &mu_right)?;
+    let mu_left_right = mul_images(&mu_left, &mu_right)?;
+
+    let sigma_left_sq = sub_images(&blurred(&mul_images(left, left)?)?, &mu_left_sq)?;
+    let sigma_right_sq = sub_images(&blurred(&mul_images(right, right)?)?, &mu_right_sq)?;
+    let sigma_left_right = sub_images(&blurred(&mul_images(left, right)?)?, &mu_left_right)?;
+
+    let cs = div_images(
+        &mul_add_image(&sigma_left_right, 2.0, c2)?,
+        &mul_add_image(&add_images(&sigma_left_sq, &sigma_right_sq)?, 1.0, c2)?,
+    )?;
+    let luminance = div_images(
+        &mul_add_image(&mu_left_right, 2.0, c1)?,
+        &mul_add_image(&add_images(&mu_left_sq, &mu_right_sq)?, 1.0, c1)?,
+    )?;
+    Ok((mul_images(&luminance, &cs)?, cs))
+}
+
+fn blurred(image: &Mat) -> Result<Mat> {
+    let mut clone = image.clone();
+    gaussian_blur(
+        image,
+        &mut clone,
+        Size::new(11, 11),
+        1.5,
+        1.5,
+        BORDER_DEFAULT,
+    )?;
+    Ok(clone)
+}
+
+fn half_size(image: &Mat) -> Result<Mat> {
+    let mut clone = image.clone();
+    pyr_down(
+        image,
+        &mut clone,
+        Size::new((image.cols() + 1) / 2, (image.rows() + 1) / 2),
+        BORDER_DEFAULT,
+    )?;
+    Ok(clone)
+}
+
+fn to_float(image: &Mat) -> Result<Mat> {
+    let mut clone = image.clone();
+    image.convert_to(&mut clone, CV_32F, 1.0, 0.0)?;
+    Ok(clone)
+}
+
+// Averaged over all channels.
+fn mean_value(image: &Mat) -> Result<f64> {
+    let channels = channels(image)?;
+    let means = mean(image, &no_mask()?)?;
+    Ok((0..channels as usize).map(|i| means[i]).sum::<f64>() / channels as f64)
+}
+
+// An empty mask, which opencv treats like `no_array()`.
+fn no_mask() -> Result<Mat> {
+    Mat::zeros(0, 0, CV_8U)?.to_mat()
+}
+
+fn mul_add_image(image: &Mat, mul: f64, add: f64) -> Result<Mat> {
+    let mut clone = image.clone();
+    image.convert_to(&mut clone, CV_32F, mul, add)?;
+    Ok(clone)
+}
+
+fn mul_images(left: &Mat, right: &Mat) -> Result<Mat> {
+    let mut clone = left.clone();
+    multiply(left, right, &mut clone, 1.0, -1)?;
+    Ok(clone)
+}
+
+fn div_images(left: &Mat, right: &Mat) -> Result<Mat> {
+    let mut clone = left.clone();
+    divide2(left, right, &mut clone, 1.0, -1)?;
+    Ok(clone)
+}
+
+fn add_images(left: &Mat, right: &Mat) -> Result<Mat> {
+    let mut clone = left.clone();
+    add(left, right, &mut clone, &no_mask()?, -1)?;
+    Ok(clone)
+}
+
+fn sub_images(left: &Mat, right: &Mat) -> Result<Mat> {
+    let mut clone = left.clone();
+    subtract(left, right, &mut clone, &no_mask()?, -1)?;
+    Ok(clone)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ramp(rows: i32, cols: i32, ripple: f32) -> Result<Mat> {
+        let mut image = Mat::zeros(rows, cols, CV_32F)?.to_mat()?;
+        for i in 0..rows {
+            for j in 0..cols {
+                *image.at_2d_mut::<f32>(i, j)? =
+                    (i + j) as f32 / (rows + cols) as f32 + ripple * ((i * 7 + j * 3) % 5) as f32;
+            }
+        }
+        Ok(image)
+    }
+
+    #[test]
+    fn identical_images_score_perfectly() -> Result<()> {
+        let image = ramp(64, 64, 0.05)?;
+        assert_eq!(mse(&image, &image)?, 0.0);
+        assert_eq!(psnr(&image, &image, 1.0)?, f64::INFINITY);
+        assert!((ssim(&image, &image, 1.0)? - 1.0).abs() < 1e-6);
+        assert!((ms_ssim(&image, &image, 1.0)? - 1.0).abs() < 1e-6);
+        Ok(())
+    }
+
+    #[test]
+    fn psnr_of_a_constant_offset() -> Result<()> {
+        let image = ramp(16, 16, 0.0)?;
+        let offset = mul_add_image(&image, 1.0, 0.1)?;
+        assert!((mse(&image, &offset)? - 0.01).abs() < 1e-6);
+        assert!((psnr(&image, &offset, 1.0)? - 20.0).abs() < 1e-3);
+        Ok(())
+    }
+
+    // Below 32 pixels only one scale is evaluated, and with renormalised
+    // weights that scale is plain SSIM.
+    #[test]
+    fn ms_ssim_on_a_single_scale_is_ssim() -> Result<()> {
+        let left = ramp(24, 24, 0.0)?;
+        let right = ramp(24, 24, 0.05)?;
+        let single = ssim(&left, &right, 1.0)?;
+        assert!(single > 0.0 && single < 1.0);
+        assert!((ms_ssim(&left, &right, 1.0)? - single).abs() < 1e-9);
+        Ok(())
+    }
+
+    #[test]
+    fn mean_value_averages_all_channels() -> Result<()> {
+        let image = Mat::new_rows_cols_with_default(
+            4,
+            4,
+            opencv::core::CV_32FC3,
+            opencv::core::Scalar::new(0.0, 0.3, 0.6, 0.0),
+        )?;
+        assert!((mean_value(&image)? - 0.3).abs() < 1e-6);
+        Ok(())
+    }
+}
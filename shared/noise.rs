@@ -0,0 +1,337 @@
+use std::f64::consts::PI;
+
+use opencv::{
+    core::{subtract, sum_elems, Mat, Point, Scalar, BORDER_REFLECT, CV_32F, CV_32FC1, CV_8U},
+    imgproc::{circle, filter_2d, line, FILLED, LINE_8},
+    prelude::*,
+    Result,
+};
+
+use crate::{
+    channels,
+    normalization::{normalize, Normalization},
+};
+
+#[derive(Debug, Clone, Copy)]
+pub enum Noise {
+    Gaussian {
+        sigma: f64,
+    },
+    // Every pixel becomes a Poisson count with mean `value / peak * photons`.
+    Poisson {
+        photons: f64,
+    },
+    SaltAndPepper {
+        amount: f64,
+        salt_ratio: f64,
+    },
+    // f + f·n with n ~ N(0, sigma²).
+    Speckle {
+        sigma: f64,
+    },
+    // Frequencies are in cycles per pixel along x and y.
+    Periodic {
+        amplitude: f64,
+        fx: f64,
+        fy: f64,
+        phase: f64,
+    },
+    // Length in pixels, angle in degrees counter-clockwise from the x axis.
+    MotionBlur {
+        length: f64,
+        angle: f64,
+    },
+    Defocus {
+        radius: f64,
+    },
+    // Isotropic, `sigma` in pixels.
+    GaussianBlur {
+        sigma: f64,
+    },
+}
+
+// `noise` is the difference between `image` and the clean input; blurs also
+// carry the point spread function that produced them.
+pub struct Degradation {
+    pub model: Noise,
+    pub image: Mat,
+    pub noise: Mat,
+    pub psf: Option<Mat>,
+}
+
+impl Degradation {
+    // Root mean square of `noise`, in the units of the image.
+    pub fn noise_rms(&self) -> Result<f64> {
+        let noise = self.noise.reshape(1, 0)?;
+        let values = noise.data_typed::<f32>()?;
+        let sum = values
+            .iter()
+            .map(|&value| (value as f64).powi(2))
+            .sum::<f64>();
+        Ok((sum / values.len().max(1) as f64).sqrt())
+    }
+}
+
+// Images are expected as CV_32F in [0, `peak`]; the result is clipped to the
+// same range. Equal seeds give equal degradations.
+pub fn degrade(image: &Mat, model: Noise, seed: u64, peak: f64) -> Result<Degradation> {
+    let clean = {
+        let mut clone = image.clone();
+        image.convert_to(&mut clone, CV_32F, 1.0, 0.0)?;
+        clone
+    };
+    let mut rng = SplitMix64::new(seed);
+
+    let (degraded, psf) = match model {
+        Noise::MotionBlur { length, angle } => {
+            let psf = motion_psf(length, angle)?;
+            (blurred(&clean, &psf)?, Some(psf))
+        }
+        Noise::Defocus { radius } => {
+            let psf = defocus_psf(radius)?;
+            (blurred(&clean, &psf)?, Some(psf))
+        }
+        Noise::GaussianBlur { sigma } => {
+            let psf = gaussian_psf(sigma)?;
+            (blurred(&clean, &psf)?, Some(psf))
+        }
+        _ => {
+            let mut degraded = clean.clone();
+            let channels = channels(&clean)? as usize;
+            let row_len = clean.cols() as usize * channels;
+            let mut values = degraded.reshape(1, 0)?;
+            for (i, value) in values.data_typed_mut::<f32>()?.iter_mut().enumerate() {
+                let (x, y) = ((i % row_len / channels) as f64, (i / row_len) as f64);
+                *value = noisy(*value as f64, model, x, y, &mut rng, peak) as f32;
+            }
+            (degraded, None)
+        }
+    };
+
+    let (degraded, _) = normalize(
+        &degraded,
+        Normalization::Fixed {
+            min: 0.0,
+            max: peak,
+        },
+        peak,
+    )?;
+    let mut noise = degraded.clone();
+    let no_mask = Mat::zeros(0, 0, CV_8U)?.to_mat()?;
+    subtract(&degraded, &clean, &mut noise, &no_mask, CV_32F)?;
+
+    Ok(Degradation {
+        model,
+        image: degraded,
+        noise,
+        psf,
+    })
+}
+
+pub fn motion_psf(length: f64, angle: f64) -> Result<Mat> {
+    let size = (length.ceil() as i32 | 1).max(1);
+    let mut psf = Mat::zeros(size, size, CV_32FC1)?.to_mat()?;
+    let center = size as f64 / 2.0 - 0.5;
+    let (dx, dy) = (
+        angle.to_radians().cos() * length / 2.0,
+        -angle.to_radians().sin() * length / 2.0,
+    );
+    line(
+        &mut psf,
+        Point::new((center - dx).round() as i32, (center - dy).round() as i32),
+        Point::new((center + dx).round() as i32, (center + dy).round() as i32),
+        Scalar::all(1.0),
+        1,
+        LINE_8,
+        0,
+    )?;
+    unit_sum(&psf)
+}
+
+pub fn defocus_psf(radius: f64) -> Result<Mat> {
+    let size = 2 * radius.ceil() as i32 + 1;
+    let mut psf = Mat::zeros(size, size, CV_32FC1)?.to_mat()?;
+    circle(
+        &mut psf,
+        Point::new(size / 2, size / 2),
+        radius.round() as i32,
+        Scalar::all(1.0),
+        FILLED,
+        LINE_8,
+        0,
+    )?;
+    unit_sum(&psf)
+}
+
+pub fn gaussian_psf(sigma: f64) -> Result<Mat> {
+    let size = 2 * (3.0 * sigma).ceil() as i32 + 1;
+    let mut psf = Mat::zeros(size, size, CV_32FC1)?.to_mat()?;
+    let center = (size / 2) as f64;
+    for i in 0..size {
+        for j in 0..size {
+            let dist = (i as f64 - center).powi(2) + (j as f64 - center).powi(2);
+            *psf.at_2d_mut::<f32>(i, j)? = (-dist / (2.0 * sigma.powi(2))).exp() as f32;
+        }
+    }
+    unit_sum(&psf)
+}
+
+fn noisy(value: f64, model: Noise, x: f64, y: f64, rng: &mut SplitMix64, peak: f64) -> f64 {
+    match model {
+        Noise::Gaussian { sigma } => value + sigma * rng.normal(),
+        Noise::Poisson { photons } => rng.poisson(value / peak * photons) / photons * peak,
+        Noise::SaltAndPepper { amount, salt_ratio } => {
+            if rng.uniform() >= amount {
+                value
+            } else if rng.uniform() < salt_ratio {
+                peak
+            } else {
+                0.0
+            }
+        }
+        Noise::Speckle { sigma } => value + value * sigma * rng.normal(),
+        Noise::Periodic {
+            amplitude,
+            fx,
+            fy,
+            phase,
+        } => value + amplitude * (2.0 * PI * (fx * x + fy * y) + phase).sin(),
+        Noise::MotionBlur { .. } | Noise::Defocus { .. } | Noise::GaussianBlur { .. } => value,
+    }
+}
+
+fn blurred(image: &Mat, psf: &Mat) -> Result<Mat> {
+    let mut clone = image.clone();
+    filter_2d(
+        image,
+        &mut clone,
+        CV_32F,
+        psf,
+        Point::new(-1, -1),
+        0.0,
+        BORDER_REFLECT,
+    )?;
+    Ok(clone)
+}
+
+fn unit_sum(psf: &Mat) -> Result<Mat> {
+    let sum = sum_elems(psf)?[0];
+    let mut clone = psf.clone();
+    psf.convert_to(&mut clone, CV_32F, 1.0 / sum.max(f64::EPSILON), 0.0)?;
+    Ok(clone)
+}
+
+// Small deterministic generator so that degradations do not depend on the
+// global OpenCV RNG state.
+struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    fn new(seed: u64) -> SplitMix64 {
+        SplitMix64 { state: seed }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    // Uniform in [0, 1).
+    fn uniform(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    // Box–Muller.
+    fn normal(&mut self) -> f64 {
+        let u = 1.0 - self.uniform();
+        let v = self.uniform();
+        (-2.0 * u.ln()).sqrt() * (2.0 * PI * v).cos()
+    }
+
+    // Knuth's method for small means, a normal approximation above that.
+    fn poisson(&mut self, mean: f64) -> f64 {
+        if mean <= 0.0 {
+            return 0.0;
+        }
+        if mean > 30.0 {
+            return (mean + mean.sqrt() * self.normal()).round().max(0.0);
+        }
+        let limit = (-mean).exp();
+        let mut count = 0.0;
+        let mut product = self.uniform();
+        while product > limit {
+            count += 1.0;
+            product *= self.uniform();
+        }
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_mix_matches_the_reference_sequence() {
+        let mut rng = SplitMix64::new(0);
+        assert_eq!(rng.next_u64(), 0xE220_A839_7B1D_CDAF);
+        assert_eq!(rng.next_u64(), 0x6E78_9E6A_A1B9_65F4);
+        assert_eq!(rng.next_u64(), 0x06C4_5D18_8009_454F);
+    }
+
+    #[test]
+    fn distributions_have_the_expected_moments() {
+        let n = 20000;
+        let mut rng = SplitMix64::new(7);
+
+        let uniform = (0..n).map(|_| rng.uniform()).collect::<Vec<_>>();
+        assert!(uniform.iter().all(|&u| (0.0..1.0).contains(&u)));
+        assert!((uniform.iter().sum::<f64>() / n as f64 - 0.5).abs() < 0.01);
+
+        let normal = (0..n).map(|_| rng.normal()).collect::<Vec<_>>();
+        let mean = normal.iter().sum::<f64>() / n as f64;
+        let variance = normal.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / n as f64;
+        assert!(mean.abs() < 0.03);
+        assert!((variance - 1.0).abs() < 0.05);
+
+        for &lambda in &[0.5, 4.0, 50.0] {
+            let mean = (0..n).map(|_| rng.poisson(lambda)).sum::<f64>() / n as f64;
+            assert!((mean - lambda).abs() < 0.05 * lambda.max(1.0));
+        }
+        assert_eq!(rng.poisson(0.0), 0.0);
+    }
+
+    #[test]
+    fn equal_seeds_give_equal_degradations() -> Result<()> {
+        let image = Mat::new_rows_cols_with_default(16, 16, CV_32F, Scalar::all(0.5))?;
+        let model = Noise::Gaussian { sigma: 0.1 };
+        let first = degrade(&image, model, 3, 1.0)?;
+        let second = degrade(&image, model, 3, 1.0)?;
+        let other = degrade(&image, model, 4, 1.0)?;
+        assert_eq!(
+            first.image.data_typed::<f32>()?,
+            second.image.data_typed::<f32>()?
+        );
+        assert_ne!(
+            first.image.data_typed::<f32>()?,
+            other.image.data_typed::<f32>()?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn blur_kernels_sum_to_one() -> Result<()> {
+        for psf in &[
+            motion_psf(15.0, 30.0)?,
+            defocus_psf(4.0)?,
+            gaussian_psf(2.0)?,
+        ] {
+            assert!((sum_elems(psf)?[0] - 1.0).abs() < 1e-5);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,225 @@
+use std::fmt;
+
+use opencv::{
+    core::{patch_na_ns, Mat, CV_32F},
+    imgproc::{threshold, THRESH_TOZERO, THRESH_TRUNC},
+    prelude::*,
+    Result,
+};
+
+#[derive(Debug, Clone, Copy)]
+pub enum Normalization {
+    MinMax,
+    // Both bounds are in percent, e.g. `{ low: 1.0, high: 99.0 }`.
+    Percentile { low: f64, high: f64 },
+    MeanStd { k: f64 },
+    Fixed { min: f64, max: f64 },
+}
+
+impl Normalization {
+    pub const NAMES: &'static [&'static str] = &["minmax", "percentile", "meanstd", "fixed"];
+
+    pub fn by_name(name: &str) -> Option<Normalization> {
+        match name {
+            "minmax" => Some(Normalization::MinMax),
+            "percentile" => Some(Normalization::Percentile {
+                low: 1.0,
+                high: 99.0,
+            }),
+            "meanstd" => Some(Normalization::MeanStd { k: 3.0 }),
+            "fixed" => Some(Normalization::Fixed { min: 0.0, max: 1.0 }),
+            _ => None,
+        }
+    }
+}
+
+// `min` is mapped to 0 and `max` to `scale`; everything outside is clipped.
+// A degenerate mapping (constant image, empty image, no finite values)
+// produces an all-zero result instead of NaN/inf.
+#[derive(Debug, Clone, Copy)]
+pub struct Mapping {
+    pub mode: Normalization,
+    pub min: f64,
+    pub max: f64,
+    pub scale: f64,
+    pub clipped: f64,
+    pub degenerate: bool,
+}
+
+impl fmt::Display for Mapping {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:?}: [{}, {}] -> [0, {}], {:.2}% clipped",
+            self.mode,
+            self.min,
+            self.max,
+            self.scale,
+            self.clipped * 100.0
+        )?;
+        if self.degenerate {
+            write!(f, " (degenerate range)")?;
+        }
+        Ok(())
+    }
+}
+
+pub fn normalize(image: &Mat, mode: Normalization, scale: f64) -> Result<(Mat, Mapping)> {
+    let image = {
+        let mut clone = image.clone();
+        image.convert_to(&mut clone, CV_32F, 1.0, 0.0)?;
+        patch_na_ns(&mut clone, 0.0)?;
+        clone
+    };
+
+    let mut values = finite_values(&image)?;
+    let (min, max) = match mode {
+        Normalization::MinMax => values
+            .iter()
+            .fold(None, |acc: Option<(f32, f32)>, &value| match acc {
+                Some((min, max)) => Some((min.min(value), max.max(value))),
+                None => Some((value, value)),
+            })
+            .map(|(min, max)| (min as f64, max as f64))
+            .unwrap_or((0.0, 0.0)),
+        Normalization::Percentile { low, high } => {
+            values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            (percentile(&values, low), percentile(&values, high))
+        }
+        Normalization::MeanStd { k } => {
+            let count = values.len().max(1) as f64;
+            let mean = values.iter().map(|&value| value as f64).sum::<f64>() / count;
+            let variance = values
+                .iter()
+                .map(|&value| (value as f64 - mean).powi(2))
+                .sum::<f64>()
+                / count;
+            (mean - k * variance.sqrt(), mean + k * variance.sqrt())
+        }
+        Normalization::Fixed { min, max } => (min, max),
+    };
+
+    let degenerate = !(max - min).is_finite() || max - min <= f64::EPSILON * max.abs().max(1.0);
+    let clipped = values
+        .iter()
+        .filter(|&&value| (value as f64) < min || (value as f64) > max)
+        .count() as f64
+        / values.len().max(1) as f64;
+    let mapping = Mapping {
+        mode,
+        min,
+        max,
+        scale,
+        clipped: if degenerate { 0.0 } else { clipped },
+        degenerate,
+    };
+
+    if degenerate {
+        let mut result = image.clone();
+        image.convert_to(&mut result, CV_32F, 0.0, 0.0)?;
+        return Ok((result, mapping));
+    }
+
+    let mut clone = image.clone();
+    image.convert_to(
+        &mut clone,
+        CV_32F,
+        scale / (max - min),
+        -min * scale / (max - min),
+    )?;
+    Ok((clamp_image(&clone, scale)?, mapping))
+}
+
+fn clamp_image(image: &Mat, max: f64) -> Result<Mat> {
+    let mut truncated = image.clone();
+    threshold(image, &mut truncated, max, max, THRESH_TRUNC)?;
+    let mut clone = image.clone();
+    threshold(&truncated, &mut clone, 0.0, 0.0, THRESH_TOZERO)?;
+    Ok(clone)
+}
+
+fn finite_values(image: &Mat) -> Result<Vec<f32>> {
+    let image = image.reshape(1, 0)?;
+    Ok(image
+        .data_typed::<f32>()?
+        .iter()
+        .copied()
+        .filter(|value| value.is_finite())
+        .collect())
+}
+
+fn percentile(sorted: &[f32], percent: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let position = (percent.max(0.0).min(100.0) / 100.0) * (sorted.len() - 1) as f64;
+    let (lower, upper) = (position.floor() as usize, position.ceil() as usize);
+    let fraction = position - lower as f64;
+    sorted[lower] as f64 * (1.0 - fraction) + sorted[upper] as f64 * fraction
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opencv::core::Scalar;
+
+    fn ramp_with_hot_pixel() -> Result<Mat> {
+        let mut image = Mat::zeros(20, 20, CV_32F)?.to_mat()?;
+        for i in 0..400 {
+            *image.at_2d_mut::<f32>(i / 20, i % 20)? = i as f32 / 399.0;
+        }
+        *image.at_2d_mut::<f32>(5, 5)? = 1000.0;
+        Ok(image)
+    }
+
+    #[test]
+    fn percentile_interpolates_between_samples() {
+        let sorted = [0.0, 1.0, 2.0, 3.0, 4.0];
+        assert_eq!(percentile(&sorted, 0.0), 0.0);
+        assert_eq!(percentile(&sorted, 50.0), 2.0);
+        assert!((percentile(&sorted, 62.5) - 2.5).abs() < 1e-12);
+        assert_eq!(percentile(&sorted, 150.0), 4.0);
+        assert_eq!(percentile(&[], 50.0), 0.0);
+    }
+
+    #[test]
+    fn hot_pixel_crushes_min_max_but_not_percentile() -> Result<()> {
+        let image = ramp_with_hot_pixel()?;
+        let (_, min_max) = normalize(&image, Normalization::MinMax, 1.0)?;
+        assert_eq!(min_max.max, 1000.0);
+
+        let (result, mapping) = normalize(
+            &image,
+            Normalization::Percentile {
+                low: 1.0,
+                high: 99.0,
+            },
+            1.0,
+        )?;
+        assert!(mapping.max < 1.5);
+        assert!(mapping.clipped > 0.0 && mapping.clipped < 0.05);
+        assert_eq!(*result.at_2d::<f32>(5, 5)?, 1.0);
+        assert!(*result.at_2d::<f32>(10, 0)? > 0.4);
+        Ok(())
+    }
+
+    #[test]
+    fn fixed_range_keeps_values_and_clips_outliers() -> Result<()> {
+        let image = ramp_with_hot_pixel()?;
+        let (result, mapping) = normalize(&image, Normalization::by_name("fixed").unwrap(), 1.0)?;
+        assert_eq!((mapping.min, mapping.max), (0.0, 1.0));
+        assert!((mapping.clipped - 1.0 / 400.0).abs() < 1e-9);
+        assert_eq!(*result.at_2d::<f32>(5, 5)?, 1.0);
+        assert_eq!(*result.at_2d::<f32>(10, 0)?, 200.0 / 399.0);
+        Ok(())
+    }
+
+    #[test]
+    fn constant_image_is_degenerate() -> Result<()> {
+        let image = Mat::new_rows_cols_with_default(4, 4, CV_32F, Scalar::all(0.5))?;
+        let (result, mapping) = normalize(&image, Normalization::MinMax, 255.0)?;
+        assert!(mapping.degenerate);
+        assert_eq!(*result.at_2d::<f32>(2, 2)?, 0.0);
+        Ok(())
+    }
+}
@@ -0,0 +1,143 @@
+use opencv::{
+    core::{Mat, Size},
+    prelude::*,
+    Result,
+};
+
+use crate::{
+    channels,
+    cli::show,
+    export::{write_contact_sheet, write_gif, write_multipage_tiff},
+    metrics::{ms_ssim, mse, psnr, ssim, tenengrad, variance_of_laplacian},
+};
+
+// Every stage is shown as soon as it is added. Reports compare each stage
+// against the first earlier stage with the same size and number of channels,
+// i.e. its input, and skip stages without one; the first stage is the input
+// of the whole pipeline and is compared against itself. Stages are expected
+// in [0, `peak`].
+pub struct Pipeline {
+    stages: Vec<(String, Mat)>,
+    peak: f64,
+}
+
+impl Pipeline {
+    pub fn new(peak: f64) -> Pipeline {
+        Pipeline {
+            stages: vec![],
+            peak,
+        }
+    }
+
+    pub fn stage(&mut self, name: &str, image: &Mat) -> Result<()> {
+        show(name, image, self.peak)?;
+        self.stages.push((name.to_string(), image.clone()));
+        Ok(())
+    }
+
+    pub fn report(&self) -> Result<()> {
+        println!(
+            "{:<24} {:>10} {:>8} {:>7} {:>7} {:>12} {:>12}",
+            "stage", "mse", "psnr", "ssim", "ms-ssim", "var(lap)", "tenengrad"
+        );
+        for (index, input) in self.comparisons()? {
+            let (name, image) = &self.stages[index];
+            let input = &self.stages[input].1;
+            println!(
+                "{:<24} {:>10.2} {:>8.2} {:>7.4} {:>7.4} {:>12.2} {:>12.2}",
+                name,
+                mse(input, image)?,
+                psnr(input, image, self.peak)?,
+                ssim(input, image, self.peak)?,
+                ms_ssim(input, image, self.peak)?,
+                variance_of_laplacian(image)?,
+                tenengrad(image)?,
+            );
+        }
+        Ok(())
+    }
+
+    // The format follows the extension: `.gif` steps through the stages,
+    // `.tif`/`.tiff` writes one page per stage, anything else is a contact sheet.
+    pub fn export(&self, path: &str) -> Result<()> {
+        let lower = path.to_lowercase();
+        if lower.ends_with(".gif") {
+            write_gif(path, &self.stages, Size::new(480, 480), 150, self.peak)
+        } else if lower.ends_with(".tif") || lower.ends_with(".tiff") {
+            write_multipage_tiff(path, &self.stages, self.peak)
+        } else {
+            write_contact_sheet(path, &self.stages, 4, self.peak)
+        }
+    }
+
+    // (stage, input) pairs of indices, in the order of the stages.
+    fn comparisons(&self) -> Result<Vec<(usize, usize)>> {
+        let mut comparisons = vec![];
+        for (index, (_, image)) in self.stages.iter().enumerate() {
+            if index == 0 {
+                comparisons.push((0, 0));
+                continue;
+            }
+            for (input, (_, stage)) in self.stages[..index].iter().enumerate() {
+                if stage.size()? == image.size()? && channels(stage)? == channels(image)? {
+                    comparisons.push((index, input));
+                    break;
+                }
+            }
+        }
+        Ok(comparisons)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use opencv::core::{Scalar, CV_32F, CV_32FC3};
+
+    use super::*;
+
+    #[test]
+    fn stages_without_an_input_are_skipped() -> Result<()> {
+        let image =
+            |rows, cols, typ| Mat::new_rows_cols_with_default(rows, cols, typ, Scalar::all(0.5));
+        // Stages are pushed directly, `stage` would open a window.
+        let mut pipeline = Pipeline::new(1.0);
+        for (name, stage) in vec![
+            ("input", image(8, 8, CV_32F)?),
+            ("filtered", image(8, 8, CV_32F)?),
+            ("spectrum", image(16, 16, CV_32F)?),
+            ("color", image(8, 8, CV_32FC3)?),
+            ("color filtered", image(8, 8, CV_32FC3)?),
+            ("filtered again", image(8, 8, CV_32F)?),
+        ] {
+            pipeline.stages.push((name.to_string(), stage));
+        }
+        assert_eq!(
+            pipeline.comparisons()?,
+            vec![(0, 0), (1, 0), (4, 3), (5, 0)]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn export_picks_the_writer_by_extension() -> Result<()> {
+        let mut pipeline = Pipeline::new(1.0);
+        for (name, value) in vec![("dark", 0.2), ("bright", 0.8)] {
+            let image = Mat::new_rows_cols_with_default(8, 8, CV_32F, Scalar::all(value))?;
+            pipeline.stages.push((name.to_string(), image));
+        }
+        let dir = std::env::temp_dir().join(format!("pipeline-export-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for (file, magic) in vec![
+            ("stages.gif", &b"GIF89a"[..]),
+            ("stages.TIFF", &b"II*\0"[..]),
+            ("stages.png", &b"\x89PNG"[..]),
+        ] {
+            let path = dir.join(file);
+            pipeline.export(path.to_str().unwrap())?;
+            let bytes = std::fs::read(&path).unwrap();
+            assert!(bytes.starts_with(magic), "{}", file);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+        Ok(())
+    }
+}
@@ -0,0 +1,223 @@
+use opencv::{
+    core::{self, absdiff, add_weighted, subtract, Mat, Size, BORDER_DEFAULT, CV_32F, CV_8U},
+    imgproc::{gaussian_blur, laplacian, threshold, THRESH_BINARY},
+    prelude::*,
+    Error, Result,
+};
+
+use crate::{
+    fft::{fft_complex, ifft_complex},
+    filters::{apply_filter, FrequencyFilter, HighEmphasis},
+    mul_mat_image, new_mat,
+    normalization::{normalize, Normalization},
+};
+
+#[derive(Debug, Clone, Copy)]
+pub enum Sharpen {
+    // f + amount·(f - blur(f)), ignoring details weaker than `threshold`, a
+    // fraction of the peak.
+    Unsharp {
+        amount: f64,
+        radius: f64,
+        threshold: f64,
+    },
+    // (boost·f - blur(f)) / (boost - 1) for `boost` > 1, i.e. f plus the
+    // high-pass divided by (boost - 1): flat regions keep their value.
+    HighBoost {
+        boost: f64,
+        radius: f64,
+    },
+    // f - amount·∇²f with the 4-neighbour Laplacian.
+    Laplacian {
+        amount: f64,
+    },
+    // Frequency filter low + high·(1 - H_lp) with the low-pass `filter`.
+    HighFrequencyEmphasis {
+        low: f64,
+        high: f64,
+        filter: HighEmphasis,
+    },
+}
+
+impl Sharpen {
+    pub const NAMES: &'static [&'static str] = &["unsharp", "highboost", "laplacian", "emphasis"];
+
+    pub fn by_name(name: &str) -> Option<Sharpen> {
+        match name {
+            "unsharp" => Some(Sharpen::Unsharp {
+                amount: 1.5,
+                radius: 2.0,
+                threshold: 0.02,
+            }),
+            "highboost" => Some(Sharpen::HighBoost {
+                boost: 1.8,
+                radius: 2.0,
+            }),
+            "laplacian" => Some(Sharpen::Laplacian { amount: 1.0 }),
+            "emphasis" => Some(Sharpen::HighFrequencyEmphasis {
+                low: 0.5,
+                high: 1.5,
+                filter: HighEmphasis::Gaussian { radius: 30.0 },
+            }),
+            _ => None,
+        }
+    }
+}
+
+// Works on CV_32F images in [0, `peak`] and clamps the result to the same
+// range.
+pub fn sharpen(image: &Mat, method: &Sharpen, peak: f64) -> Result<Mat> {
+    let result = match *method {
+        Sharpen::Unsharp {
+            amount,
+            radius,
+            threshold: min_detail,
+        } => {
+            let detail = high_pass(image, radius)?;
+            let detail = if min_detail > 0.0 {
+                mul_mat_image(&detail, &detail_mask(&detail, min_detail * peak)?)?
+            } else {
+                detail
+            };
+            let mut clone = new_mat();
+            add_weighted(image, 1.0, &detail, amount, 0.0, &mut clone, CV_32F)?;
+            clone
+        }
+        Sharpen::HighBoost { boost, radius } => {
+            if boost <= 1.0 {
+                return Err(Error::new(
+                    core::StsBadArg,
+                    format!("high-boost needs a boost above 1, not {}", boost),
+                ));
+            }
+            let mut clone = new_mat();
+            add_weighted(
+                image,
+                1.0,
+                &high_pass(image, radius)?,
+                1.0 / (boost - 1.0),
+                0.0,
+                &mut clone,
+                CV_32F,
+            )?;
+            clone
+        }
+        Sharpen::Laplacian { amount } => {
+            let mut image_laplacian = new_mat();
+            laplacian(
+                image,
+                &mut image_laplacian,
+                CV_32F,
+                1,
+                1.0,
+                0.0,
+                BORDER_DEFAULT,
+            )?;
+            let mut clone = new_mat();
+            add_weighted(
+                image,
+                1.0,
+                &image_laplacian,
+                -amount,
+                0.0,
+                &mut clone,
+                CV_32F,
+            )?;
+            clone
+        }
+        Sharpen::HighFrequencyEmphasis { low, high, filter } => {
+            let fft = fft_complex(image)?;
+            let filter = filter.low_pass().complement().affine(high, low);
+            ifft_complex(&apply_filter(&fft, &filter)?, image.size()?)?
+        }
+    };
+    Ok(normalize(
+        &result,
+        Normalization::Fixed {
+            min: 0.0,
+            max: peak,
+        },
+        peak,
+    )?
+    .0)
+}
+
+fn high_pass(image: &Mat, radius: f64) -> Result<Mat> {
+    let mut blurred = new_mat();
+    gaussian_blur(
+        image,
+        &mut blurred,
+        Size::new(0, 0),
+        radius,
+        radius,
+        BORDER_DEFAULT,
+    )?;
+    let mut clone = new_mat();
+    let no_mask = Mat::zeros(0, 0, CV_8U)?.to_mat()?;
+    subtract(image, &blurred, &mut clone, &no_mask, CV_32F)?;
+    Ok(clone)
+}
+
+// 1 where |detail| exceeds `min_detail`, 0 elsewhere.
+fn detail_mask(detail: &Mat, min_detail: f64) -> Result<Mat> {
+    let zeros = Mat::zeros(detail.rows(), detail.cols(), CV_32F)?.to_mat()?;
+    let mut abs_detail = new_mat();
+    absdiff(detail, &zeros, &mut abs_detail)?;
+    let mut mask = new_mat();
+    threshold(&abs_detail, &mut mask, min_detail, 1.0, THRESH_BINARY)?;
+    Ok(mask)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(value: f32) -> Result<Mat> {
+        let mut image = Mat::zeros(32, 32, CV_32F)?.to_mat()?;
+        for i in 0..32 {
+            for j in 0..32 {
+                *image.at_2d_mut::<f32>(i, j)? = if j < 16 { 0.2 } else { value };
+            }
+        }
+        Ok(image)
+    }
+
+    #[test]
+    fn high_boost_keeps_flat_regions() -> Result<()> {
+        let method = Sharpen::HighBoost {
+            boost: 1.8,
+            radius: 2.0,
+        };
+        let image = sharpen(&step(0.2)?, &method, 1.0)?;
+        for value in image.data_typed::<f32>()? {
+            assert!((value - 0.2).abs() < 1e-5, "{}", value);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn high_boost_overshoots_at_an_edge() -> Result<()> {
+        let method = Sharpen::HighBoost {
+            boost: 1.8,
+            radius: 2.0,
+        };
+        let image = sharpen(&step(0.6)?, &method, 1.0)?;
+        let row = (0..32)
+            .map(|j| image.at_2d::<f32>(16, j).map(|value| *value))
+            .collect::<Result<Vec<_>>>()?;
+        // Far from the edge both sides keep their level, next to it the dark
+        // side undershoots and the bright one overshoots.
+        assert!((row[0] - 0.2).abs() < 1e-3 && (row[31] - 0.6).abs() < 1e-3);
+        assert!(row[15] < 0.15 && row[16] > 0.65, "{:?}", row);
+        assert!(sharpen(
+            &step(0.6)?,
+            &Sharpen::HighBoost {
+                boost: 1.0,
+                radius: 2.0
+            },
+            1.0
+        )
+        .is_err());
+        Ok(())
+    }
+}